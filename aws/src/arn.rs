@@ -0,0 +1,327 @@
+use std::fmt;
+use std::str::FromStr;
+
+use luminary::Value;
+use miette::Diagnostic;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use crate::iam;
+
+/// The partitions AWS is split into. Each has its own `arn:<partition>:` prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Partition {
+    Aws,
+    AwsCn,
+    AwsUsGov,
+}
+
+// Deriving it needs `#[default]` on the variant, which needs Rust 1.62
+#[allow(clippy::derivable_impls)]
+impl Default for Partition {
+    fn default() -> Self {
+        Partition::Aws
+    }
+}
+
+impl Partition {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Partition::Aws => "aws",
+            Partition::AwsCn => "aws-cn",
+            Partition::AwsUsGov => "aws-us-gov",
+        }
+    }
+
+    /// The partition a region lives in, e.g. `cn-north-1` is in `aws-cn`
+    pub fn for_region(region: &str) -> Partition {
+        if region.starts_with("cn-") {
+            Partition::AwsCn
+        } else if region.starts_with("us-gov-") {
+            Partition::AwsUsGov
+        } else {
+            Partition::Aws
+        }
+    }
+}
+
+impl FromStr for Partition {
+    type Err = ArnError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "aws" => Ok(Partition::Aws),
+            "aws-cn" => Ok(Partition::AwsCn),
+            "aws-us-gov" => Ok(Partition::AwsUsGov),
+            other => Err(ArnError::UnknownPartition(other.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Error, Diagnostic, PartialEq, Eq)]
+pub enum ArnError {
+    #[error("`{0}` is not an ARN")]
+    #[diagnostic(
+        code(aws::arn::malformed),
+        help("ARNs look like `arn:partition:service:region:account:resource`")
+    )]
+    Malformed(String),
+
+    #[error("unknown partition `{0}`")]
+    #[diagnostic(
+        code(aws::arn::partition),
+        help("the partition is one of `aws`, `aws-cn` or `aws-us-gov`")
+    )]
+    UnknownPartition(String),
+
+    #[error("expected an ARN for `{expected}`, but it is for `{found}`")]
+    #[diagnostic(code(aws::arn::service))]
+    WrongService {
+        expected: &'static str,
+        found: String,
+    },
+
+    #[error("`{service}` ARNs don't have a {part}, but found `{found}`")]
+    #[diagnostic(code(aws::arn::unexpected))]
+    Unexpected {
+        service: &'static str,
+        part: &'static str,
+        found: String,
+    },
+
+    #[error("`{service}` ARNs need a {part}")]
+    #[diagnostic(code(aws::arn::missing))]
+    Missing {
+        service: &'static str,
+        part: &'static str,
+    },
+
+    #[error("`{resource}` is not a valid {kind}: {reason}")]
+    #[diagnostic(code(aws::arn::resource))]
+    InvalidResource {
+        kind: &'static str,
+        resource: String,
+        reason: String,
+    },
+}
+
+/// Types that can be pointed at by an ARN.
+/// They know their service and what a valid resource part looks like.
+pub trait ArnKind {
+    const SERVICE: &'static str;
+
+    fn validate(arn: &Arn<Self>) -> Result<(), ArnError>
+    where
+        Self: Sized;
+}
+
+#[derive(Builder, Debug, Clone)]
+#[builder(setter(strip_option, into), default)]
+pub struct Arn<T> {
+    #[builder(setter(skip))]
+    _marker: std::marker::PhantomData<T>,
+    partition: Option<Partition>,
+    service: Option<String>,
+    region: Option<String>,
+    namespace: Option<String>,
+    relative_id: Option<String>,
+}
+
+impl<T> Arn<T> {
+    pub fn partition(&self) -> Partition {
+        self.partition.unwrap_or_default()
+    }
+
+    pub fn service(&self) -> &str {
+        self.service.as_deref().unwrap_or_default()
+    }
+
+    pub fn region(&self) -> &str {
+        self.region.as_deref().unwrap_or_default()
+    }
+
+    /// The account the resource belongs to
+    pub fn namespace(&self) -> &str {
+        self.namespace.as_deref().unwrap_or_default()
+    }
+
+    pub fn relative_id(&self) -> &str {
+        self.relative_id.as_deref().unwrap_or_default()
+    }
+
+    pub fn with_partition(mut self, partition: Partition) -> Self {
+        self.partition = Some(partition);
+        self
+    }
+
+    /// Whether this ARN, which may contain `*` and `?` wildcards, covers the given ARN.
+    pub fn matches(&self, arn: &str) -> bool {
+        iam::wildcard_match(&self.to_string(), arn)
+    }
+
+    /// Checks that the ARN makes sense for a service that has neither
+    /// regions nor accounts in its ARNs, like S3 buckets.
+    pub(crate) fn expect_global(&self) -> Result<(), ArnError>
+    where
+        T: ArnKind,
+    {
+        if !self.region().is_empty() {
+            return Err(ArnError::Unexpected {
+                service: T::SERVICE,
+                part: "region",
+                found: self.region().to_string(),
+            });
+        }
+
+        if !self.namespace().is_empty() {
+            return Err(ArnError::Unexpected {
+                service: T::SERVICE,
+                part: "account",
+                found: self.namespace().to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the ARN makes sense for a global service that scopes
+    /// its resources to an account, like IAM.
+    pub(crate) fn expect_account(&self) -> Result<(), ArnError>
+    where
+        T: ArnKind,
+    {
+        if !self.region().is_empty() {
+            return Err(ArnError::Unexpected {
+                service: T::SERVICE,
+                part: "region",
+                found: self.region().to_string(),
+            });
+        }
+
+        let account = self.namespace();
+        if account.is_empty() {
+            return Err(ArnError::Missing {
+                service: T::SERVICE,
+                part: "account",
+            });
+        }
+
+        let is_account_id = account.len() == 12 && account.chars().all(|c| c.is_ascii_digit());
+        let is_pattern = account.contains(['*', '?']);
+        // AWS managed policies live in the `aws` account
+        if !is_account_id && !is_pattern && account != "aws" {
+            return Err(ArnError::InvalidResource {
+                kind: "account",
+                resource: account.to_string(),
+                reason: "account ids are 12 digits".into(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> fmt::Display for Arn<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "arn:{}:{}:{}:{}:{}",
+            self.partition().as_str(),
+            self.service(),
+            self.region(),
+            self.namespace(),
+            self.relative_id(),
+        )
+    }
+}
+
+impl<T: ArnKind> FromStr for Arn<T> {
+    type Err = ArnError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        // The resource part may contain colons of its own
+        let parts: Vec<&str> = raw.splitn(6, ':').collect();
+
+        let (partition, service, region, namespace, relative_id) = match parts.as_slice() {
+            ["arn", partition, service, region, namespace, relative_id] => {
+                (partition, service, region, namespace, relative_id)
+            }
+            _ => return Err(ArnError::Malformed(raw.to_string())),
+        };
+
+        if *service != T::SERVICE {
+            return Err(ArnError::WrongService {
+                expected: T::SERVICE,
+                found: service.to_string(),
+            });
+        }
+
+        if relative_id.is_empty() {
+            return Err(ArnError::Missing {
+                service: T::SERVICE,
+                part: "resource",
+            });
+        }
+
+        let non_empty = |part: &str| Some(part.to_string()).filter(|p| !p.is_empty());
+
+        let arn = Arn {
+            _marker: std::marker::PhantomData,
+            partition: Some(partition.parse()?),
+            service: Some(service.to_string()),
+            region: non_empty(region),
+            namespace: non_empty(namespace),
+            relative_id: Some(relative_id.to_string()),
+        };
+
+        T::validate(&arn)?;
+
+        Ok(arn)
+    }
+}
+
+/// ARNs are recorded in their usual text form, e.g. as module outputs in the state.
+impl<T> Serialize for Arn<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de, T: ArnKind> Deserialize<'de> for Arn<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        raw.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl<T> Default for Arn<T> {
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+            partition: None,
+            service: None,
+            region: None,
+            namespace: None,
+            relative_id: None,
+        }
+    }
+}
+
+impl<T> From<Arn<T>> for iam::Resource {
+    fn from(arn: Arn<T>) -> Self {
+        iam::Resource::new(arn.to_string())
+    }
+}
+
+impl<T> From<&Arn<T>> for iam::Resource {
+    fn from(arn: &Arn<T>) -> Self {
+        iam::Resource::new(arn.to_string())
+    }
+}
+
+/// So ARNs can be embedded in templates.
+impl<T> From<Arn<T>> for Value<String> {
+    fn from(arn: Arn<T>) -> Self {
+        Value::Real(arn.to_string())
+    }
+}
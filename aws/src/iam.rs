@@ -1,46 +1,338 @@
-#[derive(Debug)]
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+mod evaluator;
+mod linter;
+mod resources;
+
+pub use evaluator::{Decision, Request};
+pub(crate) use evaluator::wildcard_match;
+pub use linter::Finding;
+pub use resources::{
+    Group, GroupBuilder, Policy, PolicyBuilder, Role, RoleBuilder, RolePolicyAttachment,
+    RolePolicyAttachmentBuilder, User, UserBuilder,
+};
+
+/// The only policy language version that supports everything below.
+pub const POLICY_VERSION: &str = "2012-10-17";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PolicyDocument {
+    #[serde(rename = "Version", default = "default_version")]
+    pub version: String,
+    #[serde(rename = "Id", default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(rename = "Statement", with = "one_or_many")]
     pub statements: Vec<PolicyStatement>,
 }
 
-#[derive(Clone, Debug)]
+fn default_version() -> String {
+    POLICY_VERSION.to_string()
+}
+
+impl PolicyDocument {
+    pub fn new(statements: Vec<PolicyStatement>) -> Self {
+        PolicyDocument {
+            version: default_version(),
+            id: None,
+            statements,
+        }
+    }
+
+    /// Renders the document the way AWS expects policies to look.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("policy documents always serialize")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+impl FromStr for PolicyDocument {
+    type Err = serde_json::Error;
+
+    fn from_str(json: &str) -> Result<Self, Self::Err> {
+        PolicyDocument::from_json(json)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Effect {
     Allow,
     Deny,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Principal {
+    /// Anyone at all, written as `"Principal": "*"`.
+    /// When mixed with other principals, it wins.
+    Any,
     AWS(String),
+    Service(String),
+    Federated(String),
+    CanonicalUser(String),
+}
+
+impl Principal {
+    fn parse(kind: &str, identifier: String) -> Result<Principal, String> {
+        match kind {
+            "AWS" => Ok(Principal::AWS(identifier)),
+            "Service" => Ok(Principal::Service(identifier)),
+            "Federated" => Ok(Principal::Federated(identifier)),
+            "CanonicalUser" => Ok(Principal::CanonicalUser(identifier)),
+            other => Err(format!("unsupported principal type {}", other)),
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Action(String);
 
 impl Action {
     pub fn new<S: Into<String>>(action: S) -> Action {
         Action(action.into())
     }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Resource(String);
 
 impl Resource {
     pub fn new<S: Into<String>>(action: S) -> Resource {
         Resource(action.into())
     }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// The condition operators from the IAM policy grammar.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConditionOperator {
+    StringEquals,
+    StringNotEquals,
+    StringEqualsIgnoreCase,
+    StringNotEqualsIgnoreCase,
+    StringLike,
+    StringNotLike,
+    NumericEquals,
+    NumericNotEquals,
+    NumericLessThan,
+    NumericLessThanEquals,
+    NumericGreaterThan,
+    NumericGreaterThanEquals,
+    DateEquals,
+    DateNotEquals,
+    DateLessThan,
+    DateLessThanEquals,
+    DateGreaterThan,
+    DateGreaterThanEquals,
+    Bool,
+    BinaryEquals,
+    IpAddress,
+    NotIpAddress,
+    ArnEquals,
+    ArnLike,
+    ArnNotEquals,
+    ArnNotLike,
+    Null,
+}
+
+impl ConditionOperator {
+    const ALL: [ConditionOperator; 27] = [
+        ConditionOperator::StringEquals,
+        ConditionOperator::StringNotEquals,
+        ConditionOperator::StringEqualsIgnoreCase,
+        ConditionOperator::StringNotEqualsIgnoreCase,
+        ConditionOperator::StringLike,
+        ConditionOperator::StringNotLike,
+        ConditionOperator::NumericEquals,
+        ConditionOperator::NumericNotEquals,
+        ConditionOperator::NumericLessThan,
+        ConditionOperator::NumericLessThanEquals,
+        ConditionOperator::NumericGreaterThan,
+        ConditionOperator::NumericGreaterThanEquals,
+        ConditionOperator::DateEquals,
+        ConditionOperator::DateNotEquals,
+        ConditionOperator::DateLessThan,
+        ConditionOperator::DateLessThanEquals,
+        ConditionOperator::DateGreaterThan,
+        ConditionOperator::DateGreaterThanEquals,
+        ConditionOperator::Bool,
+        ConditionOperator::BinaryEquals,
+        ConditionOperator::IpAddress,
+        ConditionOperator::NotIpAddress,
+        ConditionOperator::ArnEquals,
+        ConditionOperator::ArnLike,
+        ConditionOperator::ArnNotEquals,
+        ConditionOperator::ArnNotLike,
+        ConditionOperator::Null,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        use ConditionOperator::*;
+
+        match self {
+            StringEquals => "StringEquals",
+            StringNotEquals => "StringNotEquals",
+            StringEqualsIgnoreCase => "StringEqualsIgnoreCase",
+            StringNotEqualsIgnoreCase => "StringNotEqualsIgnoreCase",
+            StringLike => "StringLike",
+            StringNotLike => "StringNotLike",
+            NumericEquals => "NumericEquals",
+            NumericNotEquals => "NumericNotEquals",
+            NumericLessThan => "NumericLessThan",
+            NumericLessThanEquals => "NumericLessThanEquals",
+            NumericGreaterThan => "NumericGreaterThan",
+            NumericGreaterThanEquals => "NumericGreaterThanEquals",
+            DateEquals => "DateEquals",
+            DateNotEquals => "DateNotEquals",
+            DateLessThan => "DateLessThan",
+            DateLessThanEquals => "DateLessThanEquals",
+            DateGreaterThan => "DateGreaterThan",
+            DateGreaterThanEquals => "DateGreaterThanEquals",
+            Bool => "Bool",
+            BinaryEquals => "BinaryEquals",
+            IpAddress => "IpAddress",
+            NotIpAddress => "NotIpAddress",
+            ArnEquals => "ArnEquals",
+            ArnLike => "ArnLike",
+            ArnNotEquals => "ArnNotEquals",
+            ArnNotLike => "ArnNotLike",
+            Null => "Null",
+        }
+    }
+}
+
+impl FromStr for ConditionOperator {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        ConditionOperator::ALL
+            .iter()
+            .find(|operator| operator.as_str() == raw)
+            .copied()
+            .ok_or_else(|| format!("unknown condition operator {}", raw))
+    }
+}
+
+/// How a condition treats keys with multiple values in the request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetOperator {
+    ForAllValues,
+    ForAnyValue,
+}
+
+/// A single check within a statement's `Condition` block, e.g.
+/// `"StringEquals": { "aws:SourceAccount": ["111122223333"] }`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Condition {
+    pub operator: ConditionOperator,
+    pub set_operator: Option<SetOperator>,
+    /// Written as an `IfExists` suffix on the operator:
+    /// the condition holds when the key is missing from the request.
+    pub if_exists: bool,
+    pub key: String,
+    pub values: Vec<String>,
+}
+
+impl Condition {
+    pub fn new<K, V>(operator: ConditionOperator, key: K, values: V) -> Condition
+    where
+        K: Into<String>,
+        V: IntoIterator,
+        V::Item: Into<String>,
+    {
+        Condition {
+            operator,
+            set_operator: None,
+            if_exists: false,
+            key: key.into(),
+            values: values.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn if_exists(mut self) -> Self {
+        self.if_exists = true;
+        self
+    }
+
+    pub fn for_all_values(mut self) -> Self {
+        self.set_operator = Some(SetOperator::ForAllValues);
+        self
+    }
+
+    pub fn for_any_value(mut self) -> Self {
+        self.set_operator = Some(SetOperator::ForAnyValue);
+        self
+    }
+
+    /// The full operator as it appears in JSON, e.g. `ForAnyValue:StringLikeIfExists`
+    fn qualified_operator(&self) -> String {
+        let prefix = match self.set_operator {
+            Some(SetOperator::ForAllValues) => "ForAllValues:",
+            Some(SetOperator::ForAnyValue) => "ForAnyValue:",
+            None => "",
+        };
+        let suffix = if self.if_exists { "IfExists" } else { "" };
+
+        format!("{}{}{}", prefix, self.operator.as_str(), suffix)
+    }
+
+    fn parse(qualified: &str, key: String, values: Vec<String>) -> Result<Condition, String> {
+        let (set_operator, rest) = if let Some(rest) = qualified.strip_prefix("ForAllValues:") {
+            (Some(SetOperator::ForAllValues), rest)
+        } else if let Some(rest) = qualified.strip_prefix("ForAnyValue:") {
+            (Some(SetOperator::ForAnyValue), rest)
+        } else {
+            (None, qualified)
+        };
+
+        let (if_exists, operator) = match rest.strip_suffix("IfExists") {
+            Some(operator) => (true, operator),
+            None => (false, rest),
+        };
+
+        Ok(Condition {
+            operator: operator.parse()?,
+            set_operator,
+            if_exists,
+            key,
+            values,
+        })
+    }
 }
 
-#[derive(Builder, Debug, Clone)]
+#[derive(Builder, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RawStatement", into = "RawStatement")]
 pub struct PolicyStatement {
     #[builder(default)]
     pub sid: String,
     #[builder(default = "Effect::Allow")]
     pub effect: Effect,
-    pub principal: Principal,
+    #[builder(default)]
+    pub principals: Vec<Principal>,
+    #[builder(default)]
+    pub not_principals: Vec<Principal>,
+    #[builder(default)]
     pub actions: Vec<Action>,
+    #[builder(default)]
+    pub not_actions: Vec<Action>,
+    #[builder(default)]
     pub resources: Vec<Resource>,
+    #[builder(default)]
+    pub not_resources: Vec<Resource>,
+    #[builder(default)]
+    pub conditions: Vec<Condition>,
 }
 
 impl PolicyStatementBuilder {
@@ -56,6 +348,20 @@ impl PolicyStatementBuilder {
         new
     }
 
+    pub fn principal(&mut self, principal: Principal) -> &mut Self {
+        let new = self;
+        let principals = new.principals.get_or_insert_with(Vec::new);
+        principals.push(principal);
+        new
+    }
+
+    pub fn not_principal(&mut self, principal: Principal) -> &mut Self {
+        let new = self;
+        let principals = new.not_principals.get_or_insert_with(Vec::new);
+        principals.push(principal);
+        new
+    }
+
     pub fn action(&mut self, action: Action) -> &mut Self {
         let new = self;
         let actions = new.actions.get_or_insert_with(Vec::new);
@@ -63,10 +369,576 @@ impl PolicyStatementBuilder {
         new
     }
 
-    pub fn resource(&mut self, resource: Resource) -> &mut Self {
+    pub fn not_action(&mut self, action: Action) -> &mut Self {
+        let new = self;
+        let actions = new.not_actions.get_or_insert_with(Vec::new);
+        actions.push(action);
+        new
+    }
+
+    pub fn resource<R: Into<Resource>>(&mut self, resource: R) -> &mut Self {
         let new = self;
         let resources = new.resources.get_or_insert_with(Vec::new);
-        resources.push(resource);
+        resources.push(resource.into());
+        new
+    }
+
+    pub fn not_resource<R: Into<Resource>>(&mut self, resource: R) -> &mut Self {
+        let new = self;
+        let resources = new.not_resources.get_or_insert_with(Vec::new);
+        resources.push(resource.into());
         new
     }
+
+    pub fn condition(&mut self, condition: Condition) -> &mut Self {
+        let new = self;
+        let conditions = new.conditions.get_or_insert_with(Vec::new);
+        conditions.push(condition);
+        new
+    }
+}
+
+/// Mirrors the JSON grammar of a statement so that serde can do the heavy lifting.
+/// Most of the elements can either be a single string or a list of strings.
+#[derive(Serialize, Deserialize)]
+struct RawStatement {
+    #[serde(rename = "Sid", default, skip_serializing_if = "Option::is_none")]
+    sid: Option<String>,
+    #[serde(rename = "Effect")]
+    effect: Effect,
+    #[serde(rename = "Principal", default, skip_serializing_if = "Option::is_none")]
+    principal: Option<RawPrincipal>,
+    #[serde(rename = "NotPrincipal", default, skip_serializing_if = "Option::is_none")]
+    not_principal: Option<RawPrincipal>,
+    #[serde(rename = "Action", default, skip_serializing_if = "Option::is_none")]
+    action: Option<OneOrMany>,
+    #[serde(rename = "NotAction", default, skip_serializing_if = "Option::is_none")]
+    not_action: Option<OneOrMany>,
+    #[serde(rename = "Resource", default, skip_serializing_if = "Option::is_none")]
+    resource: Option<OneOrMany>,
+    #[serde(rename = "NotResource", default, skip_serializing_if = "Option::is_none")]
+    not_resource: Option<OneOrMany>,
+    #[serde(rename = "Condition", default, skip_serializing_if = "Option::is_none")]
+    condition: Option<BTreeMap<String, BTreeMap<String, OneOrMany>>>,
+}
+
+impl TryFrom<RawStatement> for PolicyStatement {
+    type Error = String;
+
+    fn try_from(raw: RawStatement) -> Result<Self, Self::Error> {
+        if raw.action.is_some() && raw.not_action.is_some() {
+            return Err("a statement cannot have both Action and NotAction".into());
+        }
+        if raw.resource.is_some() && raw.not_resource.is_some() {
+            return Err("a statement cannot have both Resource and NotResource".into());
+        }
+        if raw.principal.is_some() && raw.not_principal.is_some() {
+            return Err("a statement cannot have both Principal and NotPrincipal".into());
+        }
+
+        let mut conditions = Vec::new();
+        for (operator, checks) in raw.condition.unwrap_or_default() {
+            for (key, values) in checks {
+                conditions.push(Condition::parse(&operator, key, values.0)?);
+            }
+        }
+
+        let list = |values: Option<OneOrMany>| values.map(|v| v.0).unwrap_or_default();
+
+        Ok(PolicyStatement {
+            sid: raw.sid.unwrap_or_default(),
+            effect: raw.effect,
+            principals: RawPrincipal::parse(raw.principal)?,
+            not_principals: RawPrincipal::parse(raw.not_principal)?,
+            actions: list(raw.action).into_iter().map(Action).collect(),
+            not_actions: list(raw.not_action).into_iter().map(Action).collect(),
+            resources: list(raw.resource).into_iter().map(Resource).collect(),
+            not_resources: list(raw.not_resource).into_iter().map(Resource).collect(),
+            conditions,
+        })
+    }
+}
+
+impl From<PolicyStatement> for RawStatement {
+    fn from(statement: PolicyStatement) -> Self {
+        let some = |values: Vec<String>| {
+            if values.is_empty() {
+                None
+            } else {
+                Some(OneOrMany(values))
+            }
+        };
+
+        let mut condition: BTreeMap<String, BTreeMap<String, OneOrMany>> = BTreeMap::new();
+        for c in statement.conditions {
+            condition
+                .entry(c.qualified_operator())
+                .or_default()
+                .entry(c.key)
+                .or_insert_with(|| OneOrMany(Vec::new()))
+                .0
+                .extend(c.values);
+        }
+
+        RawStatement {
+            sid: Some(statement.sid).filter(|sid| !sid.is_empty()),
+            effect: statement.effect,
+            principal: RawPrincipal::render(statement.principals),
+            not_principal: RawPrincipal::render(statement.not_principals),
+            action: some(statement.actions.into_iter().map(|a| a.0).collect()),
+            not_action: some(statement.not_actions.into_iter().map(|a| a.0).collect()),
+            resource: some(statement.resources.into_iter().map(|r| r.0).collect()),
+            not_resource: some(statement.not_resources.into_iter().map(|r| r.0).collect()),
+            condition: Some(condition).filter(|c| !c.is_empty()),
+        }
+    }
+}
+
+/// Either `"*"` or a map of principal types to their identifiers.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawPrincipal {
+    Any(String),
+    Typed(BTreeMap<String, OneOrMany>),
+}
+
+impl RawPrincipal {
+    fn parse(raw: Option<RawPrincipal>) -> Result<Vec<Principal>, String> {
+        match raw {
+            None => Ok(Vec::new()),
+            Some(RawPrincipal::Any(any)) if any == "*" => Ok(vec![Principal::Any]),
+            Some(RawPrincipal::Any(other)) => Err(format!(
+                "principals must be \"*\" or a map of identifiers, got {:?}",
+                other
+            )),
+            Some(RawPrincipal::Typed(typed)) => {
+                let mut principals = Vec::new();
+                for (kind, identifiers) in typed {
+                    for identifier in identifiers.0 {
+                        principals.push(Principal::parse(&kind, identifier)?);
+                    }
+                }
+                Ok(principals)
+            }
+        }
+    }
+
+    fn render(principals: Vec<Principal>) -> Option<RawPrincipal> {
+        if principals.is_empty() {
+            return None;
+        }
+        if principals.contains(&Principal::Any) {
+            return Some(RawPrincipal::Any("*".into()));
+        }
+
+        let mut typed: BTreeMap<String, OneOrMany> = BTreeMap::new();
+        for principal in principals {
+            let (kind, identifier) = match principal {
+                Principal::Any => unreachable!("handled above"),
+                Principal::AWS(identifier) => ("AWS", identifier),
+                Principal::Service(identifier) => ("Service", identifier),
+                Principal::Federated(identifier) => ("Federated", identifier),
+                Principal::CanonicalUser(identifier) => ("CanonicalUser", identifier),
+            };
+            typed
+                .entry(kind.to_string())
+                .or_insert_with(|| OneOrMany(Vec::new()))
+                .0
+                .push(identifier);
+        }
+
+        Some(RawPrincipal::Typed(typed))
+    }
+}
+
+/// A list of strings that is written as a bare string when it only has one entry,
+/// which is also how AWS hands policies back to us.
+/// Condition values are sometimes written as booleans or numbers, which we keep as text.
+struct OneOrMany(Vec<String>);
+
+impl Serialize for OneOrMany {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0.as_slice() {
+            [single] => serializer.serialize_str(single),
+            many => many.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for OneOrMany {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        use serde_json::Value;
+
+        fn text<E: Error>(value: Value) -> Result<String, E> {
+            match value {
+                Value::String(s) => Ok(s),
+                Value::Bool(b) => Ok(b.to_string()),
+                Value::Number(n) => Ok(n.to_string()),
+                other => Err(E::custom(format!("expected a string, got {}", other))),
+            }
+        }
+
+        match Value::deserialize(deserializer)? {
+            Value::Array(values) => Ok(OneOrMany(
+                values.into_iter().map(text).collect::<Result<_, _>>()?,
+            )),
+            single => Ok(OneOrMany(vec![text(single)?])),
+        }
+    }
+}
+
+/// `Statement` can either be a single statement or a list of them.
+mod one_or_many {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::PolicyStatement;
+
+    pub fn serialize<S: Serializer>(
+        statements: &[PolicyStatement],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        statements.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<PolicyStatement>, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Statements {
+            One(PolicyStatement),
+            Many(Vec<PolicyStatement>),
+        }
+
+        Ok(match Statements::deserialize(deserializer)? {
+            Statements::One(statement) => vec![statement],
+            Statements::Many(statements) => statements,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    /// Parses a policy and writes it back out again, expecting to see the same JSON.
+    fn round_trip(json: &str) -> PolicyDocument {
+        let document = PolicyDocument::from_json(json).expect("sample policy should parse");
+        let rendered = document.to_json();
+
+        let expected: Value = serde_json::from_str(json).unwrap();
+        let actual: Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(actual, expected);
+
+        assert_eq!(PolicyDocument::from_json(&rendered).unwrap(), document);
+        document
+    }
+
+    #[test]
+    fn public_read_bucket_policy() {
+        let document = round_trip(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [{
+                    "Sid": "PublicReadGetObject",
+                    "Effect": "Allow",
+                    "Principal": { "AWS": "*" },
+                    "Action": "s3:GetObject",
+                    "Resource": "arn:aws:s3:::DOC-EXAMPLE-BUCKET/*"
+                }]
+            }"#,
+        );
+
+        let statement = &document.statements[0];
+        assert_eq!(statement.sid, "PublicReadGetObject");
+        assert_eq!(statement.effect, Effect::Allow);
+        assert_eq!(statement.principals, vec![Principal::AWS("*".into())]);
+        assert_eq!(statement.actions, vec![Action::new("s3:GetObject")]);
+    }
+
+    #[test]
+    fn restricting_access_by_ip_address() {
+        let document = round_trip(
+            r#"{
+                "Version": "2012-10-17",
+                "Id": "S3PolicyId1",
+                "Statement": [{
+                    "Sid": "IPAllow",
+                    "Effect": "Deny",
+                    "Principal": { "AWS": "*" },
+                    "Action": "s3:*",
+                    "Resource": [
+                        "arn:aws:s3:::DOC-EXAMPLE-BUCKET",
+                        "arn:aws:s3:::DOC-EXAMPLE-BUCKET/*"
+                    ],
+                    "Condition": {
+                        "NotIpAddress": { "aws:SourceIp": "54.240.143.0/24" }
+                    }
+                }]
+            }"#,
+        );
+
+        assert_eq!(document.id.as_deref(), Some("S3PolicyId1"));
+        assert_eq!(
+            document.statements[0].conditions,
+            vec![Condition::new(
+                ConditionOperator::NotIpAddress,
+                "aws:SourceIp",
+                vec!["54.240.143.0/24"]
+            )]
+        );
+    }
+
+    #[test]
+    fn cross_account_access_with_many_principals() {
+        let document = round_trip(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [{
+                    "Sid": "CrossAccountList",
+                    "Effect": "Allow",
+                    "Principal": {
+                        "AWS": [
+                            "arn:aws:iam::111122223333:root",
+                            "arn:aws:iam::444455556666:user/Dave"
+                        ]
+                    },
+                    "Action": ["s3:GetBucketLocation", "s3:ListBucket"],
+                    "Resource": "arn:aws:s3:::DOC-EXAMPLE-BUCKET"
+                }]
+            }"#,
+        );
+
+        assert_eq!(document.statements[0].principals.len(), 2);
+        assert_eq!(document.statements[0].actions.len(), 2);
+    }
+
+    #[test]
+    fn deny_everything_without_mfa() {
+        let document = round_trip(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [
+                    {
+                        "Sid": "AllowViewAccountInfo",
+                        "Effect": "Allow",
+                        "Action": ["iam:GetAccountPasswordPolicy", "iam:ListVirtualMFADevices"],
+                        "Resource": "*"
+                    },
+                    {
+                        "Sid": "DenyAllExceptListedIfNoMFA",
+                        "Effect": "Deny",
+                        "NotAction": [
+                            "iam:CreateVirtualMFADevice",
+                            "iam:EnableMFADevice",
+                            "iam:GetUser",
+                            "sts:GetSessionToken"
+                        ],
+                        "Resource": "*",
+                        "Condition": {
+                            "BoolIfExists": { "aws:MultiFactorAuthPresent": "false" }
+                        }
+                    }
+                ]
+            }"#,
+        );
+
+        let deny = &document.statements[1];
+        assert!(deny.principals.is_empty());
+        assert!(deny.actions.is_empty());
+        assert_eq!(deny.not_actions.len(), 4);
+        assert_eq!(
+            deny.conditions,
+            vec![Condition::new(
+                ConditionOperator::Bool,
+                "aws:MultiFactorAuthPresent",
+                vec!["false"]
+            )
+            .if_exists()]
+        );
+    }
+
+    #[test]
+    fn wildcard_and_service_principals() {
+        let document = round_trip(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [
+                    {
+                        "Sid": "PublicRead",
+                        "Effect": "Allow",
+                        "Principal": "*",
+                        "Action": "s3:GetObject",
+                        "Resource": "arn:aws:s3:::DOC-EXAMPLE-BUCKET/*"
+                    },
+                    {
+                        "Sid": "AWSCloudTrailWrite",
+                        "Effect": "Allow",
+                        "Principal": { "Service": "cloudtrail.amazonaws.com" },
+                        "Action": "s3:PutObject",
+                        "Resource": "arn:aws:s3:::DOC-EXAMPLE-BUCKET/AWSLogs/111122223333/*",
+                        "Condition": {
+                            "StringEquals": {
+                                "s3:x-amz-acl": "bucket-owner-full-control",
+                                "aws:SourceAccount": "111122223333"
+                            },
+                            "ArnLike": {
+                                "aws:SourceArn": "arn:aws:cloudtrail:us-east-1:111122223333:trail/*"
+                            }
+                        }
+                    }
+                ]
+            }"#,
+        );
+
+        assert_eq!(document.statements[0].principals, vec![Principal::Any]);
+        assert_eq!(
+            document.statements[1].principals,
+            vec![Principal::Service("cloudtrail.amazonaws.com".into())]
+        );
+
+        let operators: Vec<_> = document.statements[1]
+            .conditions
+            .iter()
+            .map(|c| c.operator)
+            .collect();
+        assert_eq!(
+            operators,
+            vec![
+                ConditionOperator::ArnLike,
+                ConditionOperator::StringEquals,
+                ConditionOperator::StringEquals
+            ]
+        );
+    }
+
+    #[test]
+    fn not_principal_with_federated_and_canonical_users() {
+        let document = round_trip(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [
+                    {
+                        "Effect": "Deny",
+                        "NotPrincipal": {
+                            "AWS": "arn:aws:iam::444455556666:root",
+                            "CanonicalUser": "79a59df900b949e55d96a1e698fbacedfd6e09d98eacf8f8d5218e7cd47ef2be"
+                        },
+                        "Action": "s3:*",
+                        "Resource": "arn:aws:s3:::DOC-EXAMPLE-BUCKET/*"
+                    },
+                    {
+                        "Effect": "Allow",
+                        "Principal": { "Federated": "cognito-identity.amazonaws.com" },
+                        "Action": "sts:AssumeRoleWithWebIdentity",
+                        "Condition": {
+                            "ForAnyValue:StringLike": {
+                                "cognito-identity.amazonaws.com:amr": "authenticated"
+                            }
+                        }
+                    }
+                ]
+            }"#,
+        );
+
+        assert_eq!(document.statements[0].not_principals.len(), 2);
+        assert!(document.statements[0].principals.is_empty());
+        assert_eq!(
+            document.statements[1].conditions[0].set_operator,
+            Some(SetOperator::ForAnyValue)
+        );
+    }
+
+    #[test]
+    fn unknown_condition_operators_are_rejected() {
+        let result = PolicyDocument::from_json(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [{
+                    "Effect": "Allow",
+                    "Action": "s3:GetObject",
+                    "Resource": "*",
+                    "Condition": { "StringSortOfEquals": { "aws:username": "dave" } }
+                }]
+            }"#,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn lenient_forms_are_accepted() {
+        let document = PolicyDocument::from_json(
+            r#"{
+                "Statement": {
+                    "Effect": "Deny",
+                    "Action": ["s3:*"],
+                    "NotResource": ["arn:aws:s3:::logs/*"],
+                    "Condition": { "Bool": { "aws:SecureTransport": false } }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(document.version, POLICY_VERSION);
+        assert_eq!(document.statements.len(), 1);
+
+        let statement = &document.statements[0];
+        assert_eq!(statement.not_resources, vec![Resource::new("arn:aws:s3:::logs/*")]);
+        assert_eq!(
+            statement.conditions,
+            vec![Condition::new(
+                ConditionOperator::Bool,
+                "aws:SecureTransport",
+                vec!["false"]
+            )]
+        );
+    }
+
+    #[test]
+    fn statements_cannot_mix_action_and_not_action() {
+        let result = PolicyDocument::from_json(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [{
+                    "Effect": "Allow",
+                    "Action": "s3:GetObject",
+                    "NotAction": "s3:PutObject",
+                    "Resource": "*"
+                }]
+            }"#,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn statements_built_in_code_serialize() {
+        let statement = PolicyStatementBuilder::default()
+            .sid("PublicReadGetObject".to_string())
+            .principal(Principal::AWS("*".into()))
+            .action(Action::new("s3:GetObject"))
+            .resource(Resource::new("arn:aws:s3:::my-site/*"))
+            .build()
+            .unwrap();
+
+        let rendered: Value =
+            serde_json::from_str(&PolicyDocument::new(vec![statement]).to_json()).unwrap();
+
+        assert_eq!(
+            rendered,
+            serde_json::json!({
+                "Version": "2012-10-17",
+                "Statement": [{
+                    "Sid": "PublicReadGetObject",
+                    "Effect": "Allow",
+                    "Principal": { "AWS": "*" },
+                    "Action": "s3:GetObject",
+                    "Resource": "arn:aws:s3:::my-site/*"
+                }]
+            })
+        );
+    }
 }
@@ -0,0 +1,618 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use super::{
+    Condition, ConditionOperator, Effect, PolicyDocument, PolicyStatement, Principal, SetOperator,
+};
+use crate::{Arn, ArnError, ArnKind, Partition};
+
+/// Someone trying to do something to a resource.
+/// The context carries the values of condition keys such as `aws:SourceIp`.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub principal: Principal,
+    pub action: String,
+    pub resource: String,
+    context: HashMap<String, Vec<String>>,
+}
+
+impl Request {
+    pub fn new(
+        principal: Principal,
+        action: impl Into<String>,
+        resource: impl Into<String>,
+    ) -> Self {
+        Request {
+            principal,
+            action: action.into(),
+            resource: resource.into(),
+            context: HashMap::new(),
+        }
+    }
+
+    /// Condition keys are case insensitive, their values are not.
+    pub fn with_context<V>(mut self, key: impl AsRef<str>, values: V) -> Self
+    where
+        V: IntoIterator,
+        V::Item: Into<String>,
+    {
+        self.context.insert(
+            key.as_ref().to_lowercase(),
+            values.into_iter().map(Into::into).collect(),
+        );
+        self
+    }
+
+    fn context(&self, key: &str) -> Option<&[String]> {
+        self.context
+            .get(&key.to_lowercase())
+            .map(Vec::as_slice)
+            .filter(|values| !values.is_empty())
+    }
+}
+
+/// The outcome of evaluating a request against a policy,
+/// along with the statement that decided it.
+#[derive(Debug, PartialEq)]
+pub enum Decision<'p> {
+    Allow(&'p PolicyStatement),
+    ExplicitDeny(&'p PolicyStatement),
+    /// No statement allowed the request, which means it is denied.
+    ImplicitDeny,
+}
+
+impl Decision<'_> {
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, Decision::Allow(_))
+    }
+}
+
+impl PolicyDocument {
+    /// Works out whether the policy allows the request, the same way AWS would
+    /// for a single policy: an explicit deny always wins over an allow.
+    pub fn evaluate(&self, request: &Request) -> Decision<'_> {
+        let mut allowed_by = None;
+
+        for statement in self.statements.iter().filter(|s| s.applies_to(request)) {
+            match statement.effect {
+                Effect::Deny => return Decision::ExplicitDeny(statement),
+                Effect::Allow => {
+                    allowed_by.get_or_insert(statement);
+                }
+            }
+        }
+
+        match allowed_by {
+            Some(statement) => Decision::Allow(statement),
+            None => Decision::ImplicitDeny,
+        }
+    }
+}
+
+impl PolicyStatement {
+    fn applies_to(&self, request: &Request) -> bool {
+        let principal = if !self.principals.is_empty() {
+            self.principals
+                .iter()
+                .any(|p| principal_matches(p, &request.principal))
+        } else if !self.not_principals.is_empty() {
+            !self
+                .not_principals
+                .iter()
+                .any(|p| principal_matches(p, &request.principal))
+        } else {
+            // Identity based policies don't name a principal
+            true
+        };
+
+        let action = if !self.not_actions.is_empty() {
+            !self
+                .not_actions
+                .iter()
+                .any(|a| action_matches(a.as_str(), &request.action))
+        } else {
+            self.actions
+                .iter()
+                .any(|a| action_matches(a.as_str(), &request.action))
+        };
+
+        let resource = if !self.resources.is_empty() {
+            self.resources
+                .iter()
+                .any(|r| wildcard_match(r.as_str(), &request.resource))
+        } else if !self.not_resources.is_empty() {
+            !self
+                .not_resources
+                .iter()
+                .any(|r| wildcard_match(r.as_str(), &request.resource))
+        } else {
+            // Trust policies don't name a resource
+            true
+        };
+
+        principal && action && resource && self.conditions.iter().all(|c| c.holds_for(request))
+    }
+}
+
+fn principal_matches(pattern: &Principal, principal: &Principal) -> bool {
+    match (pattern, principal) {
+        (Principal::Any, _) => true,
+        (Principal::AWS(pattern), _) if pattern == "*" => true,
+        (Principal::AWS(pattern), Principal::AWS(arn)) => {
+            // An account, either as its bare id or its root user, stands for everyone in it.
+            // A bare id doesn't say which partition it is in, so it covers all of them.
+            let account = match pattern.parse::<Arn<AccountRoot>>() {
+                Ok(root) => Some((Some(root.partition()), root.namespace().to_string())),
+                Err(_) if is_account_id(pattern) => Some((None, pattern.clone())),
+                Err(_) => None,
+            };
+
+            match account {
+                Some((partition, account)) => {
+                    let parts: Vec<&str> = arn.splitn(6, ':').collect();
+                    let in_partition = match (partition, parts.get(1)) {
+                        (None, _) => true,
+                        (Some(partition), Some(found)) => {
+                            found.parse::<Partition>().ok() == Some(partition)
+                        }
+                        (Some(_), None) => false,
+                    };
+
+                    *arn == account || (in_partition && parts.get(4) == Some(&account.as_str()))
+                }
+                None => pattern == arn,
+            }
+        }
+        (Principal::Service(a), Principal::Service(b))
+        | (Principal::Federated(a), Principal::Federated(b))
+        | (Principal::CanonicalUser(a), Principal::CanonicalUser(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Actions are case insensitive, e.g. `s3:get*` matches `s3:GetObject`
+fn is_account_id(id: &str) -> bool {
+    id.len() == 12 && id.chars().all(|c| c.is_ascii_digit())
+}
+
+/// The root user of an account, `arn:<partition>:iam::<account>:root`,
+/// which stands for every principal in that account.
+struct AccountRoot;
+
+impl ArnKind for AccountRoot {
+    const SERVICE: &'static str = "iam";
+
+    fn validate(arn: &Arn<Self>) -> Result<(), ArnError> {
+        arn.expect_account()?;
+
+        if arn.relative_id() != "root" {
+            return Err(ArnError::InvalidResource {
+                kind: "account root",
+                resource: arn.relative_id().to_string(),
+                reason: "the root user of an account is `root`".into(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+fn action_matches(pattern: &str, action: &str) -> bool {
+    wildcard_match(&pattern.to_lowercase(), &action.to_lowercase())
+}
+
+/// Matches `*` against any number of characters and `?` against exactly one.
+pub(crate) fn wildcard_match(pattern: &str, candidate: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let candidate: Vec<char> = candidate.chars().collect();
+
+    let (mut p, mut c) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while c < candidate.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == candidate[c]) {
+            p += 1;
+            c += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, c));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            c = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&remaining| remaining == '*')
+}
+
+impl ConditionOperator {
+    /// Negated operators hold when none of the values match.
+    fn is_negated(&self) -> bool {
+        use ConditionOperator::*;
+
+        matches!(
+            self,
+            StringNotEquals
+                | StringNotEqualsIgnoreCase
+                | StringNotLike
+                | NumericNotEquals
+                | DateNotEquals
+                | NotIpAddress
+                | ArnNotEquals
+                | ArnNotLike
+        )
+    }
+
+    /// Whether a single value from the request matches a single value from the policy,
+    /// ignoring any negation.
+    fn matches(&self, actual: &str, expected: &str) -> bool {
+        use ConditionOperator::*;
+
+        match self {
+            StringEquals | StringNotEquals | BinaryEquals => actual == expected,
+            StringEqualsIgnoreCase | StringNotEqualsIgnoreCase => {
+                actual.to_lowercase() == expected.to_lowercase()
+            }
+            StringLike | StringNotLike | ArnLike | ArnNotLike => wildcard_match(expected, actual),
+            ArnEquals | ArnNotEquals => actual == expected,
+            Bool => actual.eq_ignore_ascii_case(expected),
+            NumericEquals | NumericNotEquals => compare_numbers(actual, expected, |a, b| a == b),
+            NumericLessThan => compare_numbers(actual, expected, |a, b| a < b),
+            NumericLessThanEquals => compare_numbers(actual, expected, |a, b| a <= b),
+            NumericGreaterThan => compare_numbers(actual, expected, |a, b| a > b),
+            NumericGreaterThanEquals => compare_numbers(actual, expected, |a, b| a >= b),
+            DateEquals | DateNotEquals => compare_dates(actual, expected, |o| o.is_eq()),
+            DateLessThan => compare_dates(actual, expected, |o| o.is_lt()),
+            DateLessThanEquals => compare_dates(actual, expected, |o| o.is_le()),
+            DateGreaterThan => compare_dates(actual, expected, |o| o.is_gt()),
+            DateGreaterThanEquals => compare_dates(actual, expected, |o| o.is_ge()),
+            IpAddress | NotIpAddress => ip_in_range(actual, expected),
+            // Handled before we ever look at individual values
+            Null => false,
+        }
+    }
+}
+
+fn compare_numbers(actual: &str, expected: &str, compare: fn(f64, f64) -> bool) -> bool {
+    match (actual.parse::<f64>(), expected.parse::<f64>()) {
+        (Ok(actual), Ok(expected)) => compare(actual, expected),
+        _ => false,
+    }
+}
+
+/// Dates are either epoch seconds or ISO 8601 timestamps in UTC,
+/// which conveniently sort the same way as their text.
+fn compare_dates(actual: &str, expected: &str, compare: fn(std::cmp::Ordering) -> bool) -> bool {
+    match (actual.parse::<f64>(), expected.parse::<f64>()) {
+        (Ok(actual), Ok(expected)) => {
+            matches!(actual.partial_cmp(&expected), Some(ordering) if compare(ordering))
+        }
+        (Err(_), Err(_)) => compare(actual.cmp(expected)),
+        _ => false,
+    }
+}
+
+fn ip_in_range(actual: &str, cidr: &str) -> bool {
+    let (network, prefix) = match cidr.split_once('/') {
+        Some((network, prefix)) => (network, prefix.parse::<u32>().ok()),
+        None => (cidr, None),
+    };
+
+    match (actual.parse::<IpAddr>(), network.parse::<IpAddr>()) {
+        (Ok(IpAddr::V4(actual)), Ok(IpAddr::V4(network))) => {
+            let prefix = prefix.unwrap_or(32).min(32);
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(actual) & mask == u32::from(network) & mask
+        }
+        (Ok(IpAddr::V6(actual)), Ok(IpAddr::V6(network))) => {
+            let prefix = prefix.unwrap_or(128).min(128);
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(actual) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+impl Condition {
+    fn holds_for(&self, request: &Request) -> bool {
+        let actual = request.context(&self.key);
+
+        if self.operator == ConditionOperator::Null {
+            let should_be_missing = self.values.iter().any(|v| v.eq_ignore_ascii_case("true"));
+            return actual.is_none() == should_be_missing;
+        }
+
+        let actual = match actual {
+            Some(actual) => actual,
+            None if self.if_exists => return true,
+            None => {
+                return match self.set_operator {
+                    Some(SetOperator::ForAllValues) => true,
+                    Some(SetOperator::ForAnyValue) => false,
+                    None => self.operator.is_negated(),
+                }
+            }
+        };
+
+        let check = |value: &String| {
+            let matched = self
+                .values
+                .iter()
+                .any(|expected| self.operator.matches(value, expected));
+            matched != self.operator.is_negated()
+        };
+
+        match self.set_operator {
+            Some(SetOperator::ForAllValues) => actual.iter().all(check),
+            Some(SetOperator::ForAnyValue) => actual.iter().any(check),
+            None if self.operator.is_negated() => actual.iter().all(check),
+            None => actual.iter().any(check),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn website_policy() -> PolicyDocument {
+        PolicyDocument::from_json(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [
+                    {
+                        "Sid": "PublicRead",
+                        "Effect": "Allow",
+                        "Principal": "*",
+                        "Action": "s3:Get*",
+                        "Resource": "arn:aws:s3:::my-site/*"
+                    },
+                    {
+                        "Sid": "DeployFromTheOffice",
+                        "Effect": "Allow",
+                        "Principal": { "AWS": "111122223333" },
+                        "Action": ["s3:PutObject", "s3:DeleteObject"],
+                        "Resource": "arn:aws:s3:::my-site/*",
+                        "Condition": { "IpAddress": { "aws:SourceIp": "203.0.113.0/24" } }
+                    },
+                    {
+                        "Sid": "NoPlainHttp",
+                        "Effect": "Deny",
+                        "Principal": "*",
+                        "Action": "s3:*",
+                        "Resource": ["arn:aws:s3:::my-site", "arn:aws:s3:::my-site/*"],
+                        "Condition": { "Bool": { "aws:SecureTransport": "false" } }
+                    }
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn anyone() -> Principal {
+        Principal::AWS("arn:aws:iam::999999999999:user/mallory".into())
+    }
+
+    fn deployer() -> Principal {
+        Principal::AWS("arn:aws:iam::111122223333:role/deployer".into())
+    }
+
+    fn sid<'p>(decision: &Decision<'p>) -> Option<&'p str> {
+        match decision {
+            Decision::Allow(statement) | Decision::ExplicitDeny(statement) => {
+                Some(statement.sid.as_str())
+            }
+            Decision::ImplicitDeny => None,
+        }
+    }
+
+    #[test]
+    fn anyone_can_read_over_https() {
+        let policy = website_policy();
+        let request = Request::new(anyone(), "s3:GetObject", "arn:aws:s3:::my-site/index.html")
+            .with_context("aws:SecureTransport", vec!["true"]);
+
+        let decision = policy.evaluate(&request);
+
+        assert!(decision.is_allowed());
+        assert_eq!(sid(&decision), Some("PublicRead"));
+    }
+
+    #[test]
+    fn explicit_deny_wins_over_allow() {
+        let policy = website_policy();
+        let request = Request::new(anyone(), "s3:GetObject", "arn:aws:s3:::my-site/index.html")
+            .with_context("aws:SecureTransport", vec!["false"]);
+
+        let decision = policy.evaluate(&request);
+
+        assert!(matches!(decision, Decision::ExplicitDeny(_)));
+        assert_eq!(sid(&decision), Some("NoPlainHttp"));
+    }
+
+    #[test]
+    fn nothing_matching_is_an_implicit_deny() {
+        let policy = website_policy();
+        let request = Request::new(anyone(), "s3:PutObject", "arn:aws:s3:::my-site/index.html")
+            .with_context("aws:SecureTransport", vec!["true"]);
+
+        assert_eq!(policy.evaluate(&request), Decision::ImplicitDeny);
+
+        let other_bucket = Request::new(anyone(), "s3:GetObject", "arn:aws:s3:::other/index.html")
+            .with_context("aws:SecureTransport", vec!["true"]);
+
+        assert_eq!(policy.evaluate(&other_bucket), Decision::ImplicitDeny);
+    }
+
+    #[test]
+    fn accounts_cover_their_roles_and_conditions_apply() {
+        let policy = website_policy();
+        let from_the_office = Request::new(
+            deployer(),
+            "s3:PutObject",
+            "arn:aws:s3:::my-site/index.html",
+        )
+        .with_context("aws:SecureTransport", vec!["true"])
+        .with_context("aws:SourceIp", vec!["203.0.113.42"]);
+
+        assert_eq!(
+            sid(&policy.evaluate(&from_the_office)),
+            Some("DeployFromTheOffice")
+        );
+
+        let from_home = from_the_office
+            .clone()
+            .with_context("aws:SourceIp", vec!["198.51.100.7"]);
+
+        assert_eq!(policy.evaluate(&from_home), Decision::ImplicitDeny);
+    }
+
+    #[test]
+    fn accounts_cover_their_principals_in_every_partition() {
+        let aws = |id: &str| Principal::AWS(id.into());
+        let china = aws("arn:aws-cn:iam::111122223333:role/deployer");
+        let gov_cloud = aws("arn:aws-us-gov:iam::111122223333:user/alice");
+
+        assert!(principal_matches(&aws("111122223333"), &china));
+        assert!(principal_matches(&aws("111122223333"), &gov_cloud));
+
+        let china_root = aws("arn:aws-cn:iam::111122223333:root");
+        assert!(principal_matches(&china_root, &china));
+        assert!(!principal_matches(&china_root, &gov_cloud));
+        assert!(!principal_matches(&china_root, &deployer()));
+        assert!(!principal_matches(
+            &china_root,
+            &aws("arn:aws-cn:iam::999999999999:role/deployer")
+        ));
+    }
+
+    #[test]
+    fn not_action_and_missing_keys() {
+        let policy = PolicyDocument::from_json(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [
+                    {
+                        "Sid": "Everything",
+                        "Effect": "Allow",
+                        "Action": "*",
+                        "Resource": "*"
+                    },
+                    {
+                        "Sid": "DenyAllButIamWithoutMfa",
+                        "Effect": "Deny",
+                        "NotAction": "iam:*",
+                        "Resource": "*",
+                        "Condition": { "BoolIfExists": { "aws:MultiFactorAuthPresent": "false" } }
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let without_mfa = Request::new(deployer(), "s3:ListBucket", "arn:aws:s3:::my-site")
+            .with_context("aws:MultiFactorAuthPresent", vec!["false"]);
+        assert_eq!(
+            sid(&policy.evaluate(&without_mfa)),
+            Some("DenyAllButIamWithoutMfa")
+        );
+
+        let unknown_mfa = Request::new(deployer(), "s3:ListBucket", "arn:aws:s3:::my-site");
+        assert!(matches!(
+            policy.evaluate(&unknown_mfa),
+            Decision::ExplicitDeny(_)
+        ));
+
+        let iam = Request::new(deployer(), "iam:GetUser", "*")
+            .with_context("aws:MultiFactorAuthPresent", vec!["false"]);
+        assert_eq!(sid(&policy.evaluate(&iam)), Some("Everything"));
+
+        let with_mfa = Request::new(deployer(), "S3:listbucket", "arn:aws:s3:::my-site")
+            .with_context("aws:multifactorauthpresent", vec!["true"]);
+        assert!(policy.evaluate(&with_mfa).is_allowed());
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("s3:*", "s3:GetObject"));
+        assert!(wildcard_match(
+            "arn:aws:s3:::my-site/*.html",
+            "arn:aws:s3:::my-site/a/b.html"
+        ));
+        assert!(wildcard_match(
+            "arn:aws:s3:::my-site/?.txt",
+            "arn:aws:s3:::my-site/a.txt"
+        ));
+        assert!(!wildcard_match(
+            "arn:aws:s3:::my-site/?.txt",
+            "arn:aws:s3:::my-site/ab.txt"
+        ));
+        assert!(!wildcard_match(
+            "arn:aws:s3:::my-site",
+            "arn:aws:s3:::my-site/index.html"
+        ));
+        assert!(action_matches("s3:get*", "s3:GetObject"));
+    }
+
+    #[test]
+    fn condition_operators() {
+        let request = Request::new(deployer(), "s3:GetObject", "*")
+            .with_context("s3:max-keys", vec!["10"])
+            .with_context("aws:CurrentTime", vec!["2021-06-01T12:00:00Z"])
+            .with_context("aws:SourceIp", vec!["2001:db8::1"])
+            .with_context("aws:TagKeys", vec!["team", "env"]);
+
+        let holds = |condition: Condition| condition.holds_for(&request);
+        use ConditionOperator::*;
+
+        assert!(holds(Condition::new(
+            NumericLessThanEquals,
+            "s3:max-keys",
+            vec!["10"]
+        )));
+        assert!(!holds(Condition::new(
+            NumericGreaterThan,
+            "s3:max-keys",
+            vec!["10"]
+        )));
+        assert!(holds(Condition::new(
+            DateLessThan,
+            "aws:CurrentTime",
+            vec!["2022-01-01T00:00:00Z"]
+        )));
+        assert!(holds(Condition::new(
+            IpAddress,
+            "aws:SourceIp",
+            vec!["2001:db8::/32"]
+        )));
+        assert!(holds(Condition::new(
+            Null,
+            "aws:PrincipalTag/team",
+            vec!["true"]
+        )));
+        assert!(holds(Condition::new(
+            StringNotEquals,
+            "aws:PrincipalTag/team",
+            vec!["a"]
+        )));
+        assert!(!holds(Condition::new(
+            StringEquals,
+            "aws:PrincipalTag/team",
+            vec!["a"]
+        )));
+        assert!(holds(
+            Condition::new(StringEquals, "aws:TagKeys", vec!["team", "env", "owner"])
+                .for_all_values()
+        ));
+        assert!(!holds(
+            Condition::new(StringEquals, "aws:TagKeys", vec!["team"]).for_all_values()
+        ));
+        assert!(holds(
+            Condition::new(StringEquals, "aws:TagKeys", vec!["team"]).for_any_value()
+        ));
+    }
+}
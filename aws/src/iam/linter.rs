@@ -0,0 +1,535 @@
+use std::collections::HashMap;
+
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use thiserror::Error;
+
+use super::{Effect, PolicyDocument, PolicyStatement, Principal};
+
+/// Services we expect to see as the prefix of an action, e.g. the `s3` in `s3:GetObject`.
+const KNOWN_SERVICES: &[&str] = &[
+    "acm",
+    "apigateway",
+    "athena",
+    "autoscaling",
+    "cloudformation",
+    "cloudfront",
+    "cloudtrail",
+    "cloudwatch",
+    "cognito-identity",
+    "cognito-idp",
+    "dynamodb",
+    "ec2",
+    "ecr",
+    "ecs",
+    "eks",
+    "elasticloadbalancing",
+    "events",
+    "execute-api",
+    "firehose",
+    "glue",
+    "iam",
+    "kinesis",
+    "kms",
+    "lambda",
+    "logs",
+    "organizations",
+    "rds",
+    "route53",
+    "s3",
+    "secretsmanager",
+    "ses",
+    "sns",
+    "sqs",
+    "ssm",
+    "states",
+    "sts",
+    "tag",
+];
+
+/// Something about a policy that our security team would push back on.
+/// Each finding carries the rendered policy so it can point at the offending statement.
+#[derive(Debug, Error, Diagnostic)]
+pub enum Finding {
+    #[error("{statement} allows every action of {service}")]
+    #[diagnostic(
+        code(iam::wildcard_action),
+        severity(Warning),
+        help("list the actions that are actually needed, e.g. `s3:GetObject`")
+    )]
+    WildcardAction {
+        statement: String,
+        service: String,
+        #[source_code]
+        policy: NamedSource,
+        #[label("grants `{service}`")]
+        span: SourceSpan,
+    },
+
+    #[error("{statement} applies to every resource")]
+    #[diagnostic(
+        code(iam::wildcard_resource),
+        severity(Warning),
+        help("scope the statement to specific ARNs, e.g. `arn:aws:s3:::my-bucket/*`")
+    )]
+    WildcardResource {
+        statement: String,
+        #[source_code]
+        policy: NamedSource,
+        #[label("`*` matches everything")]
+        span: SourceSpan,
+    },
+
+    #[error("{statement} allows everything except a few actions")]
+    #[diagnostic(
+        code(iam::allow_with_not_action),
+        severity(Error),
+        help("Allow with NotAction grants every action that exists now or in the future. Use Action instead")
+    )]
+    AllowWithNotAction {
+        statement: String,
+        #[source_code]
+        policy: NamedSource,
+        #[label("everything but these is allowed")]
+        span: SourceSpan,
+    },
+
+    #[error("{statement} allows anyone on the internet")]
+    #[diagnostic(
+        code(iam::public_principal),
+        severity(Warning),
+        help("name the accounts or services that need access instead of `*`")
+    )]
+    PublicPrincipal {
+        statement: String,
+        #[source_code]
+        policy: NamedSource,
+        #[label("this principal is everyone")]
+        span: SourceSpan,
+    },
+
+    #[error("{statement} uses an action from unknown service `{service}`")]
+    #[diagnostic(
+        code(iam::unknown_action_prefix),
+        severity(Warning),
+        help("check the spelling of `{action}`, actions look like `service:Action`")
+    )]
+    UnknownActionPrefix {
+        statement: String,
+        service: String,
+        action: String,
+        #[source_code]
+        policy: NamedSource,
+        #[label("unknown service")]
+        span: SourceSpan,
+    },
+
+    #[error("{statement} reuses the Sid `{sid}`")]
+    #[diagnostic(
+        code(iam::duplicate_sid),
+        severity(Error),
+        help("every statement within a policy needs its own Sid")
+    )]
+    DuplicateSid {
+        statement: String,
+        sid: String,
+        #[source_code]
+        policy: NamedSource,
+        #[label("first used here")]
+        first: SourceSpan,
+        #[label("used again here")]
+        span: SourceSpan,
+    },
+
+    #[error("{statement} does not grant or deny any action")]
+    #[diagnostic(
+        code(iam::empty_statement),
+        severity(Error),
+        help("add an Action, or remove the statement")
+    )]
+    EmptyStatement {
+        statement: String,
+        #[source_code]
+        policy: NamedSource,
+        #[label("no actions")]
+        span: SourceSpan,
+    },
+}
+
+/// The order `RawStatement` serializes elements in, so the rendering reads like the policy.
+const ELEMENTS: &[&str] = &[
+    "Sid",
+    "Effect",
+    "Principal",
+    "NotPrincipal",
+    "Action",
+    "NotAction",
+    "Resource",
+    "NotResource",
+    "Condition",
+];
+
+/// The policy rendered as pretty JSON, along with where each statement
+/// and each of its elements ended up, so findings have something to point at.
+struct Rendered {
+    text: String,
+    statements: Vec<RenderedStatement>,
+}
+
+struct RenderedStatement {
+    span: (usize, usize),
+    elements: HashMap<String, (usize, usize)>,
+}
+
+impl Rendered {
+    fn new(document: &PolicyDocument) -> Rendered {
+        let mut text = String::from("{\n");
+        text.push_str(&format!("  \"Version\": {:?},\n", document.version));
+        if let Some(id) = &document.id {
+            text.push_str(&format!("  \"Id\": {:?},\n", id));
+        }
+        text.push_str("  \"Statement\": [");
+
+        let mut statements = Vec::new();
+        for (idx, statement) in document.statements.iter().enumerate() {
+            if idx > 0 {
+                text.push(',');
+            }
+            text.push_str("\n    ");
+
+            let value =
+                serde_json::to_value(statement).expect("policy statements always serialize");
+            let object = value
+                .as_object()
+                .expect("policy statements serialize to objects");
+
+            let mut keys: Vec<&str> = ELEMENTS
+                .iter()
+                .copied()
+                .filter(|element| object.contains_key(*element))
+                .collect();
+            keys.extend(
+                object
+                    .keys()
+                    .map(String::as_str)
+                    .filter(|key| !ELEMENTS.contains(key)),
+            );
+
+            let start = text.len();
+            let mut elements = HashMap::new();
+            text.push('{');
+            for (position, key) in keys.into_iter().enumerate() {
+                if position > 0 {
+                    text.push(',');
+                }
+                text.push_str("\n      ");
+
+                let from = text.len();
+                let pretty = serde_json::to_string_pretty(&object[key])
+                    .expect("JSON values always serialize")
+                    .replace('\n', "\n      ");
+                text.push_str(&format!("{:?}: {}", key, pretty));
+                elements.insert(key.to_string(), (from, text.len()));
+            }
+            text.push_str("\n    }");
+
+            statements.push(RenderedStatement {
+                span: (start, text.len()),
+                elements,
+            });
+        }
+
+        text.push_str("\n  ]\n}\n");
+
+        Rendered { text, statements }
+    }
+
+    fn source(&self) -> NamedSource {
+        NamedSource::new("policy.json", self.text.clone())
+    }
+
+    fn statement(&self, idx: usize) -> SourceSpan {
+        let (start, end) = self.statements[idx].span;
+        (start, end - start).into()
+    }
+
+    /// Points at `"<element>": ...` within the statement,
+    /// falling back to the entire statement.
+    fn element(&self, idx: usize, element: &str) -> SourceSpan {
+        match self.statements[idx].elements.get(element) {
+            Some((start, end)) => (*start, end - start).into(),
+            None => self.statement(idx),
+        }
+    }
+}
+
+fn describe(idx: usize, statement: &PolicyStatement) -> String {
+    if statement.sid.is_empty() {
+        format!("statement #{}", idx + 1)
+    } else {
+        format!("statement #{} ({})", idx + 1, statement.sid)
+    }
+}
+
+impl PolicyDocument {
+    /// Looks for the usual suspects that make a policy broader than intended.
+    pub fn lint(&self) -> Vec<Finding> {
+        let rendered = Rendered::new(self);
+        let mut findings = Vec::new();
+        let mut sids: HashMap<&str, usize> = HashMap::new();
+
+        for (idx, statement) in self.statements.iter().enumerate() {
+            let name = describe(idx, statement);
+            let allows = statement.effect == Effect::Allow;
+
+            if !statement.sid.is_empty() {
+                if let Some(first) = sids.get(statement.sid.as_str()) {
+                    findings.push(Finding::DuplicateSid {
+                        statement: name.clone(),
+                        sid: statement.sid.clone(),
+                        policy: rendered.source(),
+                        first: rendered.element(*first, "Sid"),
+                        span: rendered.element(idx, "Sid"),
+                    });
+                } else {
+                    sids.insert(&statement.sid, idx);
+                }
+            }
+
+            if statement.actions.is_empty() && statement.not_actions.is_empty() {
+                findings.push(Finding::EmptyStatement {
+                    statement: name.clone(),
+                    policy: rendered.source(),
+                    span: rendered.statement(idx),
+                });
+            }
+
+            if allows && !statement.not_actions.is_empty() {
+                findings.push(Finding::AllowWithNotAction {
+                    statement: name.clone(),
+                    policy: rendered.source(),
+                    span: rendered.element(idx, "NotAction"),
+                });
+            }
+
+            let actions = statement.actions.iter().map(|a| ("Action", a));
+            let not_actions = statement.not_actions.iter().map(|a| ("NotAction", a));
+
+            for (element, action) in actions.chain(not_actions) {
+                let action = action.as_str();
+                let (service, name_of_action) = match action.split_once(':') {
+                    Some(parts) => parts,
+                    None => (action, ""),
+                };
+
+                // Wildcards in NotAction narrow a statement down rather than widen it
+                if allows && element == "Action" && (action == "*" || name_of_action == "*") {
+                    findings.push(Finding::WildcardAction {
+                        statement: name.clone(),
+                        service: action.to_string(),
+                        policy: rendered.source(),
+                        span: rendered.element(idx, element),
+                    });
+                } else if action != "*"
+                    && !KNOWN_SERVICES.contains(&service.to_lowercase().as_str())
+                {
+                    findings.push(Finding::UnknownActionPrefix {
+                        statement: name.clone(),
+                        service: service.to_string(),
+                        action: action.to_string(),
+                        policy: rendered.source(),
+                        span: rendered.element(idx, element),
+                    });
+                }
+            }
+
+            if allows && statement.resources.iter().any(|r| r.as_str() == "*") {
+                findings.push(Finding::WildcardResource {
+                    statement: name.clone(),
+                    policy: rendered.source(),
+                    span: rendered.element(idx, "Resource"),
+                });
+            }
+
+            let public = statement
+                .principals
+                .iter()
+                .any(|p| matches!(p, Principal::Any) || *p == Principal::AWS("*".into()));
+            if allows && public {
+                findings.push(Finding::PublicPrincipal {
+                    statement: name,
+                    policy: rendered.source(),
+                    span: rendered.element(idx, "Principal"),
+                });
+            }
+        }
+
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(findings: &[Finding]) -> Vec<String> {
+        findings
+            .iter()
+            .map(|f| f.code().map(|c| c.to_string()).unwrap_or_default())
+            .collect()
+    }
+
+    fn lint(json: &str) -> Vec<Finding> {
+        PolicyDocument::from_json(json).unwrap().lint()
+    }
+
+    #[test]
+    fn a_tight_policy_is_clean() {
+        let findings = lint(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [{
+                    "Sid": "DeployWebsite",
+                    "Effect": "Allow",
+                    "Principal": { "AWS": "arn:aws:iam::111122223333:role/deployer" },
+                    "Action": ["s3:PutObject", "s3:DeleteObject"],
+                    "Resource": "arn:aws:s3:::my-site/*"
+                }]
+            }"#,
+        );
+
+        assert!(findings.is_empty(), "{:?}", findings);
+    }
+
+    #[test]
+    fn the_classic_s3_star_on_star() {
+        let findings = lint(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [{
+                    "Sid": "YOLO",
+                    "Effect": "Allow",
+                    "Principal": "*",
+                    "Action": "s3:*",
+                    "Resource": "*"
+                }]
+            }"#,
+        );
+
+        assert_eq!(
+            codes(&findings),
+            vec![
+                "iam::wildcard_action",
+                "iam::wildcard_resource",
+                "iam::public_principal"
+            ]
+        );
+    }
+
+    #[test]
+    fn findings_point_at_the_offending_element() {
+        let document = PolicyDocument::from_json(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [
+                    { "Sid": "Fine", "Effect": "Allow", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::a/*" },
+                    { "Sid": "Broad", "Effect": "Allow", "Action": "s3:GetObject", "Resource": "*" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let rendered = Rendered::new(&document);
+        let findings = document.lint();
+
+        assert_eq!(codes(&findings), vec!["iam::wildcard_resource"]);
+        match &findings[0] {
+            Finding::WildcardResource { span, .. } => {
+                let text = &rendered.text[span.offset()..span.offset() + span.len()];
+                assert_eq!(text, "\"Resource\": \"*\"");
+            }
+            other => panic!("unexpected finding {:?}", other),
+        }
+
+        let parsed: serde_json::Value = serde_json::from_str(&rendered.text).unwrap();
+        assert_eq!(parsed["Statement"][1]["Sid"], "Broad");
+    }
+
+    #[test]
+    fn every_element_span_covers_exactly_that_element() {
+        let document = PolicyDocument::from_json(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [
+                    { "Sid": "First", "Effect": "Deny", "Action": "s3:*", "Resource": "*" },
+                    {
+                        "Sid": "Nested",
+                        "Effect": "Deny",
+                        "NotPrincipal": { "AWS": ["arn:aws:iam::111122223333:root", "arn:aws:iam::444455556666:root"] },
+                        "Action": "s3:GetObject",
+                        "NotResource": ["arn:aws:s3:::a/*", "arn:aws:s3:::b/*"],
+                        "Condition": {
+                            "StringEquals": { "aws:PrincipalTag/team": ["web", "ops"] },
+                            "Bool": { "aws:SecureTransport": "false" }
+                        }
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let rendered = Rendered::new(&document);
+        let parsed: serde_json::Value = serde_json::from_str(&rendered.text).unwrap();
+
+        for (idx, statement) in parsed["Statement"].as_array().unwrap().iter().enumerate() {
+            for (element, value) in statement.as_object().unwrap() {
+                let span = rendered.element(idx, element);
+                let text = &rendered.text[span.offset()..span.offset() + span.len()];
+                let alone: serde_json::Value =
+                    serde_json::from_str(&format!("{{{}}}", text)).unwrap();
+
+                assert_eq!(
+                    alone,
+                    serde_json::json!({ element.as_str(): value }),
+                    "{}",
+                    text
+                );
+            }
+
+            let span = rendered.statement(idx);
+            let text = &rendered.text[span.offset()..span.offset() + span.len()];
+            assert_eq!(
+                &serde_json::from_str::<serde_json::Value>(text).unwrap(),
+                statement
+            );
+        }
+
+        let span = rendered.element(1, "Condition");
+        let text = &rendered.text[span.offset()..span.offset() + span.len()];
+        assert!(text.starts_with("\"Condition\": {\n"), "{}", text);
+        assert!(text.ends_with('}'), "{}", text);
+    }
+
+    #[test]
+    fn not_action_duplicate_sids_typos_and_empty_statements() {
+        let findings = lint(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [
+                    { "Sid": "Same", "Effect": "Allow", "NotAction": "iam:*", "Resource": "arn:aws:s3:::a" },
+                    { "Sid": "Same", "Effect": "Deny", "Action": "s4:GetObject", "Resource": "arn:aws:s3:::a" },
+                    { "Effect": "Deny", "Resource": "arn:aws:s3:::a" }
+                ]
+            }"#,
+        );
+
+        assert_eq!(
+            codes(&findings),
+            vec![
+                "iam::allow_with_not_action",
+                "iam::duplicate_sid",
+                "iam::unknown_action_prefix",
+                "iam::empty_statement"
+            ]
+        );
+    }
+}
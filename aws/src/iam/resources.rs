@@ -0,0 +1,1214 @@
+use async_trait::async_trait;
+use aws_sdk_iam::{model, Client, SdkError};
+use luminary::{Creatable, CreateError, Fields, Resource, Value};
+use tracing::info;
+
+use super::PolicyDocument;
+use crate::{Arn, ArnError, ArnKind, Aws, AwsApi, LateBound, Tags};
+
+fn client(provider: &AwsApi) -> Client {
+    let config = provider.details.iam_config();
+    Client::from_conf(config)
+}
+
+/// Paths group IAM entities, and always start and end with a `/`.
+fn validate_path(path: &Option<String>) -> Result<(), String> {
+    match path {
+        Some(path) if !path.starts_with('/') || !path.ends_with('/') => Err(format!(
+            "IAM paths start and end with `/`, which {:?} does not",
+            path
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// IAM hands policy documents back URL-encoded.
+fn decode_document(raw: &str) -> Result<PolicyDocument, String> {
+    let mut decoded = Vec::with_capacity(raw.len());
+    let mut bytes = raw.bytes();
+
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex: Vec<u8> = bytes.by_ref().take(2).collect();
+            let hex = std::str::from_utf8(&hex).map_err(|e| e.to_string())?;
+            let byte = u8::from_str_radix(hex, 16)
+                .map_err(|_| format!("`%{}` is not a valid escape in a policy document", hex))?;
+            decoded.push(byte);
+        } else {
+            decoded.push(byte);
+        }
+    }
+
+    let json = String::from_utf8(decoded).map_err(|e| e.to_string())?;
+    PolicyDocument::from_json(&json)
+        .map_err(|e| format!("IAM returned an invalid policy document: {}", e))
+}
+
+fn tags_to_model(tags: &Tags) -> Vec<model::Tag> {
+    tags.iter()
+        .map(|(key, value)| model::Tag::builder().key(key).value(value).build())
+        .collect()
+}
+
+fn tags_from_model(tags: Option<Vec<model::Tag>>) -> Tags {
+    tags.unwrap_or_default()
+        .into_iter()
+        .map(|tag| (tag.key.unwrap_or_default(), tag.value.unwrap_or_default()))
+        .collect()
+}
+
+/// What every IAM ARN has in common: an account and a `<kind>/<path><name>` resource.
+fn validate_iam_arn<T: ArnKind>(arn: &Arn<T>, kind: &'static str) -> Result<(), ArnError> {
+    arn.expect_account()?;
+
+    match arn.relative_id().split_once('/') {
+        Some((prefix, rest)) if prefix == kind && !rest.is_empty() => Ok(()),
+        _ => Err(ArnError::InvalidResource {
+            kind,
+            resource: arn.relative_id().to_string(),
+            reason: format!("{}s look like `{}/<path><name>`", kind, kind),
+        }),
+    }
+}
+
+#[derive(Builder, Clone, Debug)]
+#[builder(
+    setter(strip_option, into),
+    pattern = "owned",
+    build_fn(validate = "Self::validate")
+)]
+pub struct Role {
+    pub name: String,
+    #[builder(default = "\"/\".into()")]
+    pub path: String,
+    /// Who is allowed to take on the role
+    pub assume_role_policy: PolicyDocument,
+    #[builder(default)]
+    pub description: Option<String>,
+    #[builder(default)]
+    pub tags: Tags,
+    #[builder(setter(skip))]
+    arn: LateBound,
+}
+
+impl RoleBuilder {
+    pub fn new(name: impl Into<String>) -> Self {
+        RoleBuilder::default().name(name)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_path(&self.path)
+    }
+}
+
+impl Role {
+    pub fn name(&self) -> Value<String> {
+        Value::Real(self.name.clone())
+    }
+
+    /// Only known once the role exists, as it contains the account id.
+    pub fn arn(&self) -> Value<String> {
+        self.arn.value("the ARN of a role")
+    }
+
+    fn fields(&self) -> Fields {
+        let mut fields = Fields::empty()
+            .with_text("id", self.name.clone())
+            .with_text("path", self.path.clone())
+            .with_text("assume_role_policy", self.assume_role_policy.to_json());
+        if let Some(description) = &self.description {
+            fields = fields.with_text("description", description);
+        }
+        self.tags.record(fields)
+    }
+}
+
+#[async_trait]
+impl Creatable<Aws> for Role {
+    async fn create(&self, provider: &AwsApi) -> Result<Fields, CreateError> {
+        let client = client(provider);
+
+        let mut request = client
+            .create_role()
+            .role_name(&self.name)
+            .path(&self.path)
+            .assume_role_policy_document(self.assume_role_policy.to_json());
+        if let Some(description) = &self.description {
+            request = request.description(description);
+        }
+        for tag in tags_to_model(&self.tags) {
+            request = request.tags(tag);
+        }
+
+        info!("creating role {}", self.name);
+        let output = request.send().await.map_err(|e| e.to_string())?;
+        let arn = output
+            .role
+            .and_then(|role| role.arn)
+            // It exists all the same, and can be deleted by name
+            .ok_or_else(|| {
+                CreateError::partly(self.fields(), "AWS did not return the ARN of the role")
+            })?;
+        self.arn.set(&arn);
+
+        Ok(self.fields().with_text("arn", arn))
+    }
+
+    fn kind(&self) -> &'static str {
+        "iam_role"
+    }
+}
+
+#[async_trait]
+impl Resource<Aws> for Role {
+    async fn read(&self, provider: &AwsApi, current: &Fields) -> Result<Fields, String> {
+        let client = client(provider);
+        let name = current.text("id").ok_or("no id recorded for the role")?;
+
+        info!("reading role {}", name);
+        let role = client
+            .get_role()
+            .role_name(name)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .role
+            .ok_or_else(|| format!("AWS did not describe role {}", name))?;
+
+        let tags = client
+            .list_role_tags()
+            .role_name(name)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .tags;
+
+        let arn = role.arn.unwrap_or_default();
+        self.arn.set(&arn);
+
+        let assume_role_policy =
+            decode_document(&role.assume_role_policy_document.unwrap_or_default())?;
+
+        let mut fields = Fields::empty()
+            .with_text("id", name)
+            .with_text("arn", arn)
+            .with_text("path", role.path.unwrap_or_default())
+            .with_text("assume_role_policy", assume_role_policy.to_json());
+        // AWS doesn't tell an empty description apart from a missing one
+        if let Some(description) = role.description.filter(|d| !d.is_empty()) {
+            fields = fields.with_text("description", description);
+        }
+
+        Ok(tags_from_model(tags).record(fields))
+    }
+
+    fn drifted(&self, current: &Fields) -> bool {
+        !self
+            .fields()
+            .diff(&current.clone().remove("arn"))
+            .is_empty()
+    }
+
+    async fn update(&self, provider: &AwsApi, current: &Fields) -> Result<Fields, String> {
+        if current.text("id") != Some(self.name.as_str()) {
+            return Err(format!(
+                "renaming role {:?} to {:?} requires replacing it",
+                current.text("id"),
+                self.name
+            ));
+        }
+
+        if current.text("path") != Some(self.path.as_str()) {
+            return Err(format!(
+                "moving role {} to path {} requires replacing it",
+                self.name, self.path
+            ));
+        }
+
+        let client = client(provider);
+        let desired = self.fields();
+
+        let changes = desired.diff(&current.clone().remove("arn"));
+        let changed = |name: &str| changes.iter().any(|change| change.field_name() == name);
+
+        if changed("assume_role_policy") {
+            info!("updating assume role policy of {}", self.name);
+            client
+                .update_assume_role_policy()
+                .role_name(&self.name)
+                .policy_document(self.assume_role_policy.to_json())
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        if changed("description") {
+            info!("updating description of {}", self.name);
+            client
+                .update_role()
+                .role_name(&self.name)
+                .description(self.description.clone().unwrap_or_default())
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        if changed("tags") {
+            info!("updating tags of {}", self.name);
+            let removed = self.tags.removed_since(current);
+            if !removed.is_empty() {
+                let mut request = client.untag_role().role_name(&self.name);
+                for key in removed {
+                    request = request.tag_keys(key);
+                }
+                request.send().await.map_err(|e| e.to_string())?;
+            }
+
+            if !self.tags.is_empty() {
+                let mut request = client.tag_role().role_name(&self.name);
+                for tag in tags_to_model(&self.tags) {
+                    request = request.tags(tag);
+                }
+                request.send().await.map_err(|e| e.to_string())?;
+            }
+        }
+
+        let arn = current.text("arn").ok_or("no ARN recorded for the role")?;
+        self.arn.set(arn);
+
+        Ok(desired.with_text("arn", arn))
+    }
+
+    async fn delete(&self, provider: &AwsApi, current: &Fields) -> Result<(), String> {
+        let client = client(provider);
+        let name = current.text("id").ok_or("no id recorded for the role")?;
+
+        info!("deleting role {}", name);
+        client
+            .delete_role()
+            .role_name(name)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+}
+
+impl ArnKind for Role {
+    const SERVICE: &'static str = "iam";
+
+    fn validate(arn: &Arn<Self>) -> Result<(), ArnError> {
+        validate_iam_arn(arn, "role")
+    }
+}
+
+/// A customer managed policy, which can be attached to roles, users and groups.
+#[derive(Builder, Clone, Debug)]
+#[builder(
+    setter(strip_option, into),
+    pattern = "owned",
+    build_fn(validate = "Self::validate")
+)]
+pub struct Policy {
+    pub name: String,
+    #[builder(default = "\"/\".into()")]
+    pub path: String,
+    pub document: PolicyDocument,
+    #[builder(default)]
+    pub description: Option<String>,
+    #[builder(setter(skip))]
+    arn: LateBound,
+}
+
+impl PolicyBuilder {
+    pub fn new(name: impl Into<String>) -> Self {
+        PolicyBuilder::default().name(name)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_path(&self.path)
+    }
+}
+
+/// IAM keeps at most this many versions of a managed policy around.
+const MAX_POLICY_VERSIONS: usize = 5;
+
+impl Policy {
+    pub fn arn(&self) -> Value<String> {
+        self.arn.value("the ARN of a policy")
+    }
+
+    fn fields(&self) -> Fields {
+        let mut fields = Fields::empty()
+            .with_text("id", self.name.clone())
+            .with_text("path", self.path.clone())
+            .with_text("document", self.document.to_json());
+        if let Some(description) = &self.description {
+            fields = fields.with_text("description", description);
+        }
+        fields
+    }
+
+    /// The ARN of our own policy called `name` under `path`, if there is one.
+    async fn find_arn(client: &Client, name: &str, path: &str) -> Result<Option<String>, String> {
+        let mut marker = None;
+
+        loop {
+            let output = client
+                .list_policies()
+                .scope(model::PolicyScopeType::Local)
+                .path_prefix(path)
+                .set_marker(marker)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let found = output
+                .policies
+                .unwrap_or_default()
+                .into_iter()
+                .find(|policy| {
+                    policy.policy_name.as_deref() == Some(name)
+                        && policy.path.as_deref() == Some(path)
+                })
+                .and_then(|policy| policy.arn);
+
+            // There is only a marker if there are more policies to list
+            match (found, output.marker) {
+                (Some(arn), _) => return Ok(Some(arn)),
+                (None, Some(next)) => marker = Some(next),
+                (None, None) => return Ok(None),
+            }
+        }
+    }
+
+    /// Versions other than the default one, oldest first.
+    async fn old_versions(client: &Client, arn: &str) -> Result<Vec<String>, String> {
+        let versions = client
+            .list_policy_versions()
+            .policy_arn(arn)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .versions
+            .unwrap_or_default();
+
+        let mut old: Vec<String> = versions
+            .into_iter()
+            .filter(|version| !version.is_default_version)
+            .filter_map(|version| version.version_id)
+            .collect();
+        // Versions are named v1, v2, ... so that their number gives their age
+        old.sort_by_key(|id| id.trim_start_matches('v').parse::<u32>().unwrap_or(0));
+
+        Ok(old)
+    }
+
+    async fn delete_version(client: &Client, arn: &str, version: &str) -> Result<(), String> {
+        info!("deleting version {} of {}", version, arn);
+        client
+            .delete_policy_version()
+            .policy_arn(arn)
+            .version_id(version)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Creatable<Aws> for Policy {
+    async fn create(&self, provider: &AwsApi) -> Result<Fields, CreateError> {
+        let client = client(provider);
+
+        let mut request = client
+            .create_policy()
+            .policy_name(&self.name)
+            .path(&self.path)
+            .policy_document(self.document.to_json());
+        if let Some(description) = &self.description {
+            request = request.description(description);
+        }
+
+        info!("creating policy {}", self.name);
+        let output = request.send().await.map_err(|e| e.to_string())?;
+        let arn = output
+            .policy
+            .and_then(|policy| policy.arn)
+            // It exists all the same, and can be found by name
+            .ok_or_else(|| {
+                CreateError::partly(self.fields(), "AWS did not return the ARN of the policy")
+            })?;
+        self.arn.set(&arn);
+
+        Ok(self.fields().with_text("arn", arn))
+    }
+
+    fn kind(&self) -> &'static str {
+        "iam_policy"
+    }
+}
+
+#[async_trait]
+impl Resource<Aws> for Policy {
+    async fn read(&self, provider: &AwsApi, current: &Fields) -> Result<Fields, String> {
+        let client = client(provider);
+        let arn = current
+            .text("arn")
+            .ok_or("no ARN recorded for the policy")?;
+
+        info!("reading policy {}", arn);
+        let policy = client
+            .get_policy()
+            .policy_arn(arn)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .policy
+            .ok_or_else(|| format!("AWS did not describe policy {}", arn))?;
+
+        let version = policy
+            .default_version_id
+            .ok_or_else(|| format!("policy {} has no default version", arn))?;
+        let document = client
+            .get_policy_version()
+            .policy_arn(arn)
+            .version_id(version)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .policy_version
+            .and_then(|version| version.document)
+            .ok_or_else(|| format!("AWS did not return the document of {}", arn))?;
+        let document = decode_document(&document)?;
+
+        self.arn.set(arn);
+
+        let mut fields = Fields::empty()
+            .with_text("id", policy.policy_name.unwrap_or_default())
+            .with_text("arn", arn)
+            .with_text("path", policy.path.unwrap_or_default())
+            .with_text("document", document.to_json());
+        if let Some(description) = policy.description.filter(|d| !d.is_empty()) {
+            fields = fields.with_text("description", description);
+        }
+
+        Ok(fields)
+    }
+
+    fn drifted(&self, current: &Fields) -> bool {
+        !self
+            .fields()
+            .diff(&current.clone().remove("arn"))
+            .is_empty()
+    }
+
+    async fn update(&self, provider: &AwsApi, current: &Fields) -> Result<Fields, String> {
+        let arn = current
+            .text("arn")
+            .ok_or("no ARN recorded for the policy")?;
+        let desired = self.fields();
+
+        let changes = desired.diff(&current.clone().remove("arn"));
+        let changed = |name: &str| changes.iter().any(|change| change.field_name() == name);
+
+        // Only the document of a managed policy can change in place
+        for immutable in &["id", "path", "description"] {
+            if changed(immutable) {
+                return Err(format!(
+                    "changing the {} of policy {} requires replacing it",
+                    immutable, self.name
+                ));
+            }
+        }
+
+        if changed("document") {
+            let client = client(provider);
+
+            let old = Policy::old_versions(&client, arn).await?;
+            if old.len() + 1 >= MAX_POLICY_VERSIONS {
+                Policy::delete_version(&client, arn, &old[0]).await?;
+            }
+
+            info!("updating document of {}", arn);
+            client
+                .create_policy_version()
+                .policy_arn(arn)
+                .policy_document(self.document.to_json())
+                .set_as_default(true)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        self.arn.set(arn);
+
+        Ok(desired.with_text("arn", arn))
+    }
+
+    async fn delete(&self, provider: &AwsApi, current: &Fields) -> Result<(), String> {
+        let client = client(provider);
+        let arn = match current.text("arn") {
+            Some(arn) => arn.to_string(),
+            // Creating it stopped before its ARN was known
+            None => {
+                let name = current.text("id").ok_or("no ARN recorded for the policy")?;
+                let path = current.text("path").unwrap_or("/");
+                match Policy::find_arn(&client, name, path).await? {
+                    Some(arn) => arn,
+                    None => return Ok(()),
+                }
+            }
+        };
+        let arn = arn.as_str();
+
+        // A policy can only be deleted once it's down to its default version
+        for version in Policy::old_versions(&client, arn).await? {
+            Policy::delete_version(&client, arn, &version).await?;
+        }
+
+        info!("deleting policy {}", arn);
+        client
+            .delete_policy()
+            .policy_arn(arn)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+}
+
+impl ArnKind for Policy {
+    const SERVICE: &'static str = "iam";
+
+    fn validate(arn: &Arn<Self>) -> Result<(), ArnError> {
+        validate_iam_arn(arn, "policy")
+    }
+}
+
+#[derive(Builder, Clone, Debug)]
+#[builder(setter(strip_option, into), pattern = "owned")]
+pub struct RolePolicyAttachment {
+    /// The name of the role
+    role: Value<String>,
+    policy_arn: Value<String>,
+}
+
+impl RolePolicyAttachmentBuilder {
+    pub fn new() -> Self {
+        RolePolicyAttachmentBuilder::default()
+    }
+
+    /// Attach to a role defined in the same program,
+    /// e.g. by passing the `Meta<Role>` returned from the provider.
+    pub fn role_of(self, role: &Role) -> Self {
+        self.role(role.name())
+    }
+
+    /// Attach a policy defined in the same program.
+    /// Its ARN is resolved once the policy has been created.
+    pub fn policy_of(self, policy: &Policy) -> Self {
+        self.policy_arn(policy.arn())
+    }
+}
+
+impl RolePolicyAttachment {
+    async fn attach(&self, client: &Client, role: &str, policy_arn: &str) -> Result<(), String> {
+        info!("attaching {} to {}", policy_arn, role);
+        client
+            .attach_role_policy()
+            .role_name(role)
+            .policy_arn(policy_arn)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Creatable<Aws> for RolePolicyAttachment {
+    async fn create(&self, provider: &AwsApi) -> Result<Fields, CreateError> {
+        let client = client(provider);
+        let role = self.role.get();
+        let policy_arn = self.policy_arn.get();
+
+        self.attach(&client, &role, &policy_arn).await?;
+
+        Ok(Fields::empty()
+            .with_text("role", role)
+            .with_text("policy_arn", policy_arn))
+    }
+
+    fn kind(&self) -> &'static str {
+        "iam_role_policy_attachment"
+    }
+}
+
+#[async_trait]
+impl Resource<Aws> for RolePolicyAttachment {
+    async fn read(&self, provider: &AwsApi, current: &Fields) -> Result<Fields, String> {
+        let client = client(provider);
+        let role = current
+            .text("role")
+            .ok_or("no role recorded for the attachment")?;
+        let policy_arn = current
+            .text("policy_arn")
+            .ok_or("no policy recorded for the attachment")?;
+
+        info!("reading policies attached to {}", role);
+        let attached = client
+            .list_attached_role_policies()
+            .role_name(role)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .attached_policies
+            .unwrap_or_default();
+
+        if !attached
+            .iter()
+            .any(|policy| policy.policy_arn.as_deref() == Some(policy_arn))
+        {
+            return Err(format!("{} is no longer attached to {}", policy_arn, role));
+        }
+
+        Ok(current.clone())
+    }
+
+    async fn update(&self, provider: &AwsApi, current: &Fields) -> Result<Fields, String> {
+        let role = self.role.get();
+        let policy_arn = self.policy_arn.get();
+
+        if current.text("role") == Some(role.as_str())
+            && current.text("policy_arn") == Some(policy_arn.as_str())
+        {
+            return Ok(current.clone());
+        }
+
+        self.delete(provider, current).await?;
+        Ok(self.create(provider).await?)
+    }
+
+    async fn delete(&self, provider: &AwsApi, current: &Fields) -> Result<(), String> {
+        let client = client(provider);
+        let role = current
+            .text("role")
+            .ok_or("no role recorded for the attachment")?;
+        let policy_arn = current
+            .text("policy_arn")
+            .ok_or("no policy recorded for the attachment")?;
+
+        info!("detaching {} from {}", policy_arn, role);
+        match client
+            .detach_role_policy()
+            .role_name(role)
+            .policy_arn(policy_arn)
+            .send()
+            .await
+        {
+            Ok(_) => Ok(()),
+            // Already gone, which is what we wanted
+            Err(SdkError::ServiceError { err, .. }) if err.code() == Some("NoSuchEntity") => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+#[derive(Builder, Clone, Debug)]
+#[builder(
+    setter(strip_option, into),
+    pattern = "owned",
+    build_fn(validate = "Self::validate")
+)]
+pub struct User {
+    pub name: String,
+    #[builder(default = "\"/\".into()")]
+    pub path: String,
+    #[builder(default)]
+    pub tags: Tags,
+    #[builder(setter(skip))]
+    arn: LateBound,
+}
+
+impl UserBuilder {
+    pub fn new(name: impl Into<String>) -> Self {
+        UserBuilder::default().name(name)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_path(&self.path)
+    }
+}
+
+impl User {
+    pub fn name(&self) -> Value<String> {
+        Value::Real(self.name.clone())
+    }
+
+    pub fn arn(&self) -> Value<String> {
+        self.arn.value("the ARN of a user")
+    }
+
+    fn fields(&self) -> Fields {
+        let fields = Fields::empty()
+            .with_text("id", self.name.clone())
+            .with_text("path", self.path.clone());
+        self.tags.record(fields)
+    }
+}
+
+#[async_trait]
+impl Creatable<Aws> for User {
+    async fn create(&self, provider: &AwsApi) -> Result<Fields, CreateError> {
+        let client = client(provider);
+
+        let mut request = client.create_user().user_name(&self.name).path(&self.path);
+        for tag in tags_to_model(&self.tags) {
+            request = request.tags(tag);
+        }
+
+        info!("creating user {}", self.name);
+        let output = request.send().await.map_err(|e| e.to_string())?;
+        let arn = output
+            .user
+            .and_then(|user| user.arn)
+            // It exists all the same, and can be deleted by name
+            .ok_or_else(|| {
+                CreateError::partly(self.fields(), "AWS did not return the ARN of the user")
+            })?;
+        self.arn.set(&arn);
+
+        Ok(self.fields().with_text("arn", arn))
+    }
+
+    fn kind(&self) -> &'static str {
+        "iam_user"
+    }
+}
+
+#[async_trait]
+impl Resource<Aws> for User {
+    async fn read(&self, provider: &AwsApi, current: &Fields) -> Result<Fields, String> {
+        let client = client(provider);
+        let name = current.text("id").ok_or("no id recorded for the user")?;
+
+        info!("reading user {}", name);
+        let user = client
+            .get_user()
+            .user_name(name)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .user
+            .ok_or_else(|| format!("AWS did not describe user {}", name))?;
+
+        let tags = client
+            .list_user_tags()
+            .user_name(name)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .tags;
+
+        let arn = user.arn.unwrap_or_default();
+        self.arn.set(&arn);
+
+        let fields = Fields::empty()
+            .with_text("id", name)
+            .with_text("arn", arn)
+            .with_text("path", user.path.unwrap_or_default());
+
+        Ok(tags_from_model(tags).record(fields))
+    }
+
+    fn drifted(&self, current: &Fields) -> bool {
+        !self
+            .fields()
+            .diff(&current.clone().remove("arn"))
+            .is_empty()
+    }
+
+    async fn update(&self, provider: &AwsApi, current: &Fields) -> Result<Fields, String> {
+        if current.text("id") != Some(self.name.as_str()) {
+            return Err(format!(
+                "renaming user {:?} to {:?} requires replacing it",
+                current.text("id"),
+                self.name
+            ));
+        }
+
+        let client = client(provider);
+        let desired = self.fields();
+
+        let changes = desired.diff(&current.clone().remove("arn"));
+        let changed = |name: &str| changes.iter().any(|change| change.field_name() == name);
+
+        if changed("path") {
+            info!("moving user {} to {}", self.name, self.path);
+            client
+                .update_user()
+                .user_name(&self.name)
+                .new_path(&self.path)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        if changed("tags") {
+            info!("updating tags of {}", self.name);
+            let removed = self.tags.removed_since(current);
+            if !removed.is_empty() {
+                let mut request = client.untag_user().user_name(&self.name);
+                for key in removed {
+                    request = request.tag_keys(key);
+                }
+                request.send().await.map_err(|e| e.to_string())?;
+            }
+
+            if !self.tags.is_empty() {
+                let mut request = client.tag_user().user_name(&self.name);
+                for tag in tags_to_model(&self.tags) {
+                    request = request.tags(tag);
+                }
+                request.send().await.map_err(|e| e.to_string())?;
+            }
+        }
+
+        // The ARN contains the path, so it's worth asking AWS again
+        self.read(provider, &desired).await
+    }
+
+    async fn delete(&self, provider: &AwsApi, current: &Fields) -> Result<(), String> {
+        let client = client(provider);
+        let name = current.text("id").ok_or("no id recorded for the user")?;
+
+        info!("deleting user {}", name);
+        client
+            .delete_user()
+            .user_name(name)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+}
+
+impl ArnKind for User {
+    const SERVICE: &'static str = "iam";
+
+    fn validate(arn: &Arn<Self>) -> Result<(), ArnError> {
+        validate_iam_arn(arn, "user")
+    }
+}
+
+#[derive(Builder, Clone, Debug)]
+#[builder(
+    setter(strip_option, into),
+    pattern = "owned",
+    build_fn(validate = "Self::validate")
+)]
+pub struct Group {
+    pub name: String,
+    #[builder(default = "\"/\".into()")]
+    pub path: String,
+    #[builder(setter(skip))]
+    arn: LateBound,
+}
+
+impl GroupBuilder {
+    pub fn new(name: impl Into<String>) -> Self {
+        GroupBuilder::default().name(name)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_path(&self.path)
+    }
+}
+
+impl Group {
+    pub fn name(&self) -> Value<String> {
+        Value::Real(self.name.clone())
+    }
+
+    pub fn arn(&self) -> Value<String> {
+        self.arn.value("the ARN of a group")
+    }
+
+    fn fields(&self) -> Fields {
+        Fields::empty()
+            .with_text("id", self.name.clone())
+            .with_text("path", self.path.clone())
+    }
+}
+
+#[async_trait]
+impl Creatable<Aws> for Group {
+    async fn create(&self, provider: &AwsApi) -> Result<Fields, CreateError> {
+        let client = client(provider);
+
+        info!("creating group {}", self.name);
+        let output = client
+            .create_group()
+            .group_name(&self.name)
+            .path(&self.path)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let arn = output
+            .group
+            .and_then(|group| group.arn)
+            // It exists all the same, and can be deleted by name
+            .ok_or_else(|| {
+                CreateError::partly(self.fields(), "AWS did not return the ARN of the group")
+            })?;
+        self.arn.set(&arn);
+
+        Ok(self.fields().with_text("arn", arn))
+    }
+
+    fn kind(&self) -> &'static str {
+        "iam_group"
+    }
+}
+
+#[async_trait]
+impl Resource<Aws> for Group {
+    async fn read(&self, provider: &AwsApi, current: &Fields) -> Result<Fields, String> {
+        let client = client(provider);
+        let name = current.text("id").ok_or("no id recorded for the group")?;
+
+        info!("reading group {}", name);
+        let group = client
+            .get_group()
+            .group_name(name)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .group
+            .ok_or_else(|| format!("AWS did not describe group {}", name))?;
+
+        let arn = group.arn.unwrap_or_default();
+        self.arn.set(&arn);
+
+        Ok(Fields::empty()
+            .with_text("id", name)
+            .with_text("arn", arn)
+            .with_text("path", group.path.unwrap_or_default()))
+    }
+
+    fn drifted(&self, current: &Fields) -> bool {
+        !self
+            .fields()
+            .diff(&current.clone().remove("arn"))
+            .is_empty()
+    }
+
+    async fn update(&self, provider: &AwsApi, current: &Fields) -> Result<Fields, String> {
+        if current.text("id") != Some(self.name.as_str()) {
+            return Err(format!(
+                "renaming group {:?} to {:?} requires replacing it",
+                current.text("id"),
+                self.name
+            ));
+        }
+
+        if current.text("path") != Some(self.path.as_str()) {
+            info!("moving group {} to {}", self.name, self.path);
+            client(provider)
+                .update_group()
+                .group_name(&self.name)
+                .new_path(&self.path)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        self.read(provider, &self.fields()).await
+    }
+
+    async fn delete(&self, provider: &AwsApi, current: &Fields) -> Result<(), String> {
+        let client = client(provider);
+        let name = current.text("id").ok_or("no id recorded for the group")?;
+
+        info!("deleting group {}", name);
+        client
+            .delete_group()
+            .group_name(name)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+}
+
+impl ArnKind for Group {
+    const SERVICE: &'static str = "iam";
+
+    fn validate(arn: &Arn<Self>) -> Result<(), ArnError> {
+        validate_iam_arn(arn, "group")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iam::{Action, Effect, PolicyStatementBuilder, Principal};
+    use crate::AwsDetails;
+
+    fn lambda_can_assume() -> PolicyDocument {
+        PolicyDocument::new(vec![PolicyStatementBuilder::default()
+            .allow()
+            .principal(Principal::Service("lambda.amazonaws.com".into()))
+            .action(Action::new("sts:AssumeRole"))
+            .build()
+            .unwrap()])
+    }
+
+    #[test]
+    fn documents_come_back_url_encoded() {
+        let encoded = "%7B%22Version%22%3A%222012-10-17%22%2C%22Statement%22%3A%5B%7B%22Effect%22%3A%22Allow%22%2C%22Principal%22%3A%7B%22Service%22%3A%22lambda.amazonaws.com%22%7D%2C%22Action%22%3A%22sts%3AAssumeRole%22%7D%5D%7D";
+
+        let document = decode_document(encoded).unwrap();
+        assert_eq!(document, lambda_can_assume());
+        assert_eq!(document.statements[0].effect, Effect::Allow);
+
+        assert!(decode_document("%7G").is_err());
+    }
+
+    #[test]
+    fn iam_entities_validate_their_paths_and_arns() {
+        let role = RoleBuilder::new("runner")
+            .path("/service-role")
+            .assume_role_policy(lambda_can_assume())
+            .build();
+        assert!(role.is_err());
+
+        let role = RoleBuilder::new("runner")
+            .path("/service-role/")
+            .assume_role_policy(lambda_can_assume())
+            .tags(Tags::new().with("team", "web"))
+            .build()
+            .unwrap();
+        let fields = role.fields();
+        assert_eq!(fields.text("path"), Some("/service-role/"));
+        assert_eq!(
+            fields.object("tags").and_then(|tags| tags.text("team")),
+            Some("web")
+        );
+
+        let arn: Arn<Role> = "arn:aws:iam::123456789012:role/service-role/runner"
+            .parse()
+            .unwrap();
+        assert_eq!(arn.namespace(), "123456789012");
+
+        let managed: Result<Arn<Policy>, _> = "arn:aws:iam::aws:policy/ReadOnlyAccess".parse();
+        assert!(managed.is_ok());
+
+        assert!(matches!(
+            "arn:aws:iam::123456789012:user/runner".parse::<Arn<Role>>(),
+            Err(ArnError::InvalidResource { .. })
+        ));
+        assert!(matches!(
+            "arn:aws:iam:::group/admins".parse::<Arn<Group>>(),
+            Err(ArnError::Missing {
+                part: "account",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn arns_resolve_once_the_role_exists() {
+        let role = RoleBuilder::new("runner")
+            .assume_role_policy(lambda_can_assume())
+            .build()
+            .unwrap();
+
+        let attachment = RolePolicyAttachmentBuilder::new()
+            .role_of(&role)
+            .policy_arn(role.arn())
+            .build()
+            .unwrap();
+
+        role.arn.set("arn:aws:iam::123456789012:role/runner");
+        assert_eq!(
+            attachment.policy_arn.get(),
+            "arn:aws:iam::123456789012:role/runner"
+        );
+        assert_eq!(attachment.role.get(), "runner");
+    }
+
+    #[test]
+    fn only_removed_tags_are_untagged() {
+        let current = Tags::new()
+            .with("team", "web")
+            .with("cost-center", "42")
+            .record(Fields::empty());
+        let desired = Tags::new().with("team", "platform");
+
+        assert_eq!(desired.removed_since(&current), vec!["cost-center"]);
+    }
+
+    /// Runs the whole lifecycle against a local mock of IAM, e.g. `moto_server -p 5000`,
+    /// with `AWS_ENDPOINT_URL=http://localhost:5000 cargo test -- --ignored`.
+    #[tokio::test]
+    #[ignore]
+    async fn lifecycle_against_a_local_mock() {
+        let endpoint = std::env::var("AWS_ENDPOINT_URL").expect("AWS_ENDPOINT_URL is not set");
+        let details = AwsDetails::from_keys("testing", "testing")
+            .with_endpoint(endpoint)
+            .unwrap();
+        let api = AwsApi::new(details);
+
+        let role = RoleBuilder::new("luminary-test-role")
+            .assume_role_policy(lambda_can_assume())
+            .description("created by the luminary tests")
+            .build()
+            .unwrap();
+        let policy = PolicyBuilder::new("luminary-test-policy")
+            .document(PolicyDocument::new(vec![PolicyStatementBuilder::default()
+                .allow()
+                .action(Action::new("s3:GetObject"))
+                .resource(crate::Arn::bucket("luminary-test").all_objects())
+                .build()
+                .unwrap()]))
+            .build()
+            .unwrap();
+        let attachment = RolePolicyAttachmentBuilder::new()
+            .role_of(&role)
+            .policy_of(&policy)
+            .build()
+            .unwrap();
+
+        let role_fields = role.create(&api).await.unwrap();
+        let policy_fields = policy.create(&api).await.unwrap();
+        let attachment_fields = attachment.create(&api).await.unwrap();
+
+        assert!(role.arn().get().ends_with(":role/luminary-test-role"));
+        assert!(role
+            .read(&api, &role_fields)
+            .await
+            .unwrap()
+            .diff(&role_fields)
+            .is_empty());
+        assert!(policy
+            .read(&api, &policy_fields)
+            .await
+            .unwrap()
+            .diff(&policy_fields)
+            .is_empty());
+        attachment.read(&api, &attachment_fields).await.unwrap();
+
+        attachment.delete(&api, &attachment_fields).await.unwrap();
+        policy.delete(&api, &policy_fields).await.unwrap();
+        role.delete(&api, &role_fields).await.unwrap();
+    }
+}
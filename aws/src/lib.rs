@@ -1,20 +1,23 @@
 #[macro_use]
 extern crate derive_builder;
 
-use luminary::Cloud;
+use luminary::{Cloud, Produce, Value};
 use std::collections::HashMap;
-use std::env::VarError;
 use std::fmt;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use aws_sdk_s3::{Config, Credentials};
 
+mod arn;
 pub mod iam;
 pub mod s3;
 
+pub use arn::{Arn, ArnBuilder, ArnError, ArnKind, Partition};
+
 struct Inner {
     creds: Credentials,
     region: String,
+    endpoint: Option<aws_sdk_s3::Endpoint>,
 }
 
 pub struct AwsDetails(Arc<Inner>);
@@ -24,6 +27,7 @@ impl fmt::Debug for AwsDetails {
         f.debug_struct("AwsDetails")
             .field("region", &self.0.region)
             .field("credentials", &self.0.creds)
+            .field("endpoint", &self.0.endpoint)
             .finish()
     }
 }
@@ -53,12 +57,56 @@ impl AwsApi {
     }
 
     pub fn s3_bucket(&self, name: impl Into<String>) -> s3::BucketBuilder {
-        s3::BucketBuilder::new(name)
+        s3::BucketBuilder::new(name).region(self.details.region())
+    }
+
+    /// Looks up a bucket that is not managed here.
+    pub fn data_s3_bucket(&mut self, name: impl Into<String>) -> s3::BucketData {
+        s3::BucketData::new(name)
+    }
+
+    /// Reads the outputs another program recorded for `module`
+    /// in the state it keeps at `key` in `bucket`.
+    pub fn data_s3_remote_state<T>(
+        &mut self,
+        bucket: impl Into<String>,
+        key: impl Into<String>,
+        module: impl Into<String>,
+    ) -> s3::RemoteState<T> {
+        s3::RemoteState::new(bucket, key, module)
     }
 
     pub fn s3_bucket_object(&mut self) -> s3::BucketObjectBuilder {
         s3::BucketObjectBuilder::new()
     }
+
+    pub fn s3_bucket_directory(&mut self) -> s3::BucketDirectoryBuilder {
+        s3::BucketDirectoryBuilder::new()
+    }
+
+    pub fn s3_bucket_policy(&mut self) -> s3::BucketPolicyBuilder {
+        s3::BucketPolicyBuilder::new()
+    }
+
+    pub fn iam_role(&mut self, name: impl Into<String>) -> iam::RoleBuilder {
+        iam::RoleBuilder::new(name)
+    }
+
+    pub fn iam_policy(&mut self, name: impl Into<String>) -> iam::PolicyBuilder {
+        iam::PolicyBuilder::new(name)
+    }
+
+    pub fn iam_role_policy_attachment(&mut self) -> iam::RolePolicyAttachmentBuilder {
+        iam::RolePolicyAttachmentBuilder::new()
+    }
+
+    pub fn iam_user(&mut self, name: impl Into<String>) -> iam::UserBuilder {
+        iam::UserBuilder::new(name)
+    }
+
+    pub fn iam_group(&mut self, name: impl Into<String>) -> iam::GroupBuilder {
+        iam::GroupBuilder::new(name)
+    }
 }
 
 impl AwsDetails {
@@ -69,9 +117,24 @@ impl AwsDetails {
         AwsDetails(Arc::new(Inner {
             creds: Credentials::from_keys(access_key_id, secret_access_key, None),
             region: "us-east-1".into(), // TODO: pass in
+            endpoint: None,
         }))
     }
 
+    /// Talk to something other than AWS itself, like a local mock of its APIs
+    pub fn with_endpoint(self, endpoint: impl AsRef<str>) -> Result<Self, String> {
+        let endpoint = endpoint.as_ref();
+        let uri = endpoint
+            .parse()
+            .map_err(|e| format!("endpoint {} is not a valid URI: {}", endpoint, e))?;
+
+        Ok(AwsDetails(Arc::new(Inner {
+            creds: self.creds(),
+            region: self.region(),
+            endpoint: Some(aws_sdk_s3::Endpoint::immutable(uri)),
+        })))
+    }
+
     pub fn creds(&self) -> Credentials {
         self.0.creds.clone()
     }
@@ -80,64 +143,170 @@ impl AwsDetails {
         self.0.region.clone()
     }
 
-    pub fn from_env() -> Result<Self, VarError> {
+    pub fn from_env() -> Result<Self, String> {
         dotenv::dotenv().ok();
-        let access_key_id = std::env::var("AWS_ACCESS_KEY_ID")?;
-        let secret_access_key = std::env::var("AWS_SECRET_ACCESS_KEY")?;
+        let var = |key| std::env::var(key).map_err(|e| format!("Missing env key {}: {}", key, e));
+        let access_key_id = var("AWS_ACCESS_KEY_ID")?;
+        let secret_access_key = var("AWS_SECRET_ACCESS_KEY")?;
+
+        let details = Self::from_keys(access_key_id, secret_access_key);
 
-        Ok(Self::from_keys(access_key_id, secret_access_key))
+        match std::env::var("AWS_ENDPOINT_URL") {
+            Ok(endpoint) => details.with_endpoint(endpoint),
+            Err(_) => Ok(details),
+        }
+    }
+
+    fn endpoint(&self) -> Option<aws_sdk_s3::Endpoint> {
+        self.0.endpoint.clone()
     }
 
-    fn config(&self) -> Config {
+    fn s3_config(&self) -> Config {
         let region = aws_sdk_s3::Region::new(self.region());
-        aws_sdk_s3::Config::builder()
+        let mut config = aws_sdk_s3::Config::builder()
+            .region(region)
+            .credentials_provider(self.creds());
+
+        if let Some(endpoint) = self.endpoint() {
+            config = config.endpoint_resolver(endpoint);
+        }
+
+        config.build()
+    }
+
+    fn iam_config(&self) -> aws_sdk_iam::Config {
+        let region = aws_sdk_iam::Region::new(self.region());
+        let mut config = aws_sdk_iam::Config::builder()
             .region(region)
-            .credentials_provider(self.creds())
-            .build()
+            .credentials_provider(self.creds());
+
+        if let Some(endpoint) = self.endpoint() {
+            config = config.endpoint_resolver(endpoint);
+        }
+
+        config.build()
     }
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Tags(HashMap<String, String>);
 
-#[derive(Builder, Debug, Clone)]
-#[builder(setter(strip_option, into), default)]
-pub struct Arn<T> {
-    #[builder(setter(skip))]
-    _marker: std::marker::PhantomData<T>,
-    partition: Option<String>,
-    service: Option<String>,
-    region: Option<String>,
-    namespace: Option<String>,
-    relative_id: Option<String>,
+impl Tags {
+    pub fn new() -> Self {
+        Tags::default()
+    }
+
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.insert(key, value);
+        self
+    }
+
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.0.insert(key.into(), value.into());
+    }
+
+    pub fn get(&self, key: impl AsRef<str>) -> Option<&str> {
+        self.0.get(key.as_ref()).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Records each tag as a text field within a `tags` object.
+    pub(crate) fn record(&self, fields: luminary::Fields) -> luminary::Fields {
+        fields.with_object("tags", |o| {
+            self.iter()
+                .fold(o, |o, (key, value)| o.with_text(key, value))
+        })
+    }
+
+    /// Keys that were recorded in `current` but are no longer wanted
+    pub(crate) fn removed_since(&self, current: &luminary::Fields) -> Vec<String> {
+        let empty = luminary::Fields::empty();
+        let recorded = current.object("tags").unwrap_or(&empty);
+        let desired = self.record(luminary::Fields::empty());
+        let desired = desired.object("tags").unwrap_or(&empty);
+
+        desired
+            .diff(recorded)
+            .iter()
+            .map(|difference| difference.field_name())
+            .filter(|key| self.get(key).is_none())
+            .map(String::from)
+            .collect()
+    }
 }
 
-impl<T> fmt::Display for Arn<T> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let aws = "aws".into();
-        let empty = "".into();
-
-        write!(
-            f,
-            "arn:{}:{}:{}:{}:{}",
-            self.partition.as_ref().unwrap_or(&aws),
-            self.service.as_ref().unwrap_or(&empty),
-            self.region.as_ref().unwrap_or(&empty),
-            self.namespace.as_ref().unwrap_or(&empty),
-            self.relative_id.as_ref().unwrap_or(&empty),
+impl<K, V> std::iter::FromIterator<(K, V)> for Tags
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Tags(
+            iter.into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
         )
     }
 }
 
-impl<T> Default for Arn<T> {
-    fn default() -> Self {
-        Self {
-            _marker: std::marker::PhantomData,
-            partition: None,
-            service: None,
-            region: None,
-            namespace: None,
-            relative_id: None,
+impl From<HashMap<String, String>> for Tags {
+    fn from(tags: HashMap<String, String>) -> Self {
+        Tags(tags)
+    }
+}
+
+/// Something only AWS knows once a resource exists, like the ARN of a role.
+/// Resources fill it in when they are created or read,
+/// and hand out `Value`s that resolve to it later on.
+#[derive(Clone, Default)]
+pub(crate) struct LateBound(Arc<Mutex<Option<String>>>);
+
+impl LateBound {
+    pub(crate) fn set(&self, value: impl Into<String>) {
+        *self.0.lock().unwrap() = Some(value.into());
+    }
+
+    pub(crate) fn value(&self, what: &'static str) -> Value<String> {
+        Value::Reference(Box::new(LateBoundValue {
+            cell: self.clone(),
+            what,
+        }))
+    }
+}
+
+#[derive(Clone)]
+struct LateBoundValue {
+    cell: LateBound,
+    what: &'static str,
+}
+
+impl Produce<String> for LateBoundValue {
+    fn get(&self) -> String {
+        self.try_get()
+            .unwrap_or_else(|| panic!("{} is only known once it has been created", self.what))
+    }
+
+    fn try_get(&self) -> Option<String> {
+        self.cell.0.lock().unwrap().clone()
+    }
+}
+
+impl fmt::Debug for LateBound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &*self.0.lock().unwrap() {
+            Some(value) => write!(f, "{:?}", value),
+            None => write!(f, "<unknown until created>"),
         }
     }
 }
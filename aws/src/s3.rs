@@ -1,18 +1,32 @@
 use crate::iam::PolicyDocument;
-use crate::{Arn, ArnBuilder, Aws, AwsApi, Tags};
+use crate::{Arn, ArnBuilder, ArnError, ArnKind, Aws, AwsApi, Partition, Tags};
 use async_trait::async_trait;
-use aws_sdk_s3::{ByteStream, Client};
+use aws_sdk_s3::{model, Client, SdkError};
 
-use luminary::{Creatable, Fields, Resource, Value};
+use luminary::{Creatable, CreateError, Fields, Resource, Value};
 use tracing::{info};
 
 use std::default::Default;
-use std::rc::Rc;
 
-#[derive(Clone, Debug)]
+mod cors;
+mod data;
+mod directory;
+mod lifecycle;
+mod object;
+
+pub use cors::CorsRule;
+pub use data::{BucketData, BucketDetails, RemoteState};
+pub use directory::{BucketDirectory, BucketDirectoryBuilder, CacheControl};
+pub use lifecycle::{LifecycleFilter, LifecycleRule, Transition};
+pub use object::{BucketObject, BucketObjectBuilder, Source};
+
+/// The canned ACLs a bucket or object can be created with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Acl {
     Private,
     PublicRead,
+    PublicReadWrite,
+    AuthenticatedRead,
 }
 
 impl Default for Acl {
@@ -21,45 +35,416 @@ impl Default for Acl {
     }
 }
 
+const ALL_USERS: &str = "http://acs.amazonaws.com/groups/global/AllUsers";
+const AUTHENTICATED_USERS: &str = "http://acs.amazonaws.com/groups/global/AuthenticatedUsers";
+
+impl Acl {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Acl::Private => "private",
+            Acl::PublicRead => "public-read",
+            Acl::PublicReadWrite => "public-read-write",
+            Acl::AuthenticatedRead => "authenticated-read",
+        }
+    }
+
+    fn to_model(self) -> model::BucketCannedAcl {
+        match self {
+            Acl::Private => model::BucketCannedAcl::Private,
+            Acl::PublicRead => model::BucketCannedAcl::PublicRead,
+            Acl::PublicReadWrite => model::BucketCannedAcl::PublicReadWrite,
+            Acl::AuthenticatedRead => model::BucketCannedAcl::AuthenticatedRead,
+        }
+    }
+
+    fn to_object_model(self) -> model::ObjectCannedAcl {
+        match self {
+            Acl::Private => model::ObjectCannedAcl::Private,
+            Acl::PublicRead => model::ObjectCannedAcl::PublicRead,
+            Acl::PublicReadWrite => model::ObjectCannedAcl::PublicReadWrite,
+            Acl::AuthenticatedRead => model::ObjectCannedAcl::AuthenticatedRead,
+        }
+    }
+
+    /// S3 only hands back the grants, so we work out which
+    /// canned ACL would have produced them.
+    fn from_grants(grants: &[model::Grant]) -> Acl {
+        let granted = |group: &str, permission: model::Permission| {
+            grants.iter().any(|grant| {
+                grant.permission.as_ref() == Some(&permission)
+                    && grant
+                        .grantee
+                        .as_ref()
+                        .and_then(|grantee| grantee.uri.as_deref())
+                        == Some(group)
+            })
+        };
+
+        if granted(ALL_USERS, model::Permission::Write) {
+            Acl::PublicReadWrite
+        } else if granted(ALL_USERS, model::Permission::Read) {
+            Acl::PublicRead
+        } else if granted(AUTHENTICATED_USERS, model::Permission::Read) {
+            Acl::AuthenticatedRead
+        } else {
+            Acl::Private
+        }
+    }
+}
+
 #[derive(Builder, Clone, Debug)]
-#[builder(setter(strip_option, into), pattern = "owned")]
+#[builder(
+    setter(strip_option, into),
+    pattern = "owned",
+    build_fn(validate = "Self::validate")
+)]
 pub struct Bucket {
     pub name: String,
+    #[builder(default = "\"us-east-1\".into()")]
+    pub region: String,
     #[builder(default)]
     pub acl: Acl,
     #[builder(default)]
     pub website: Option<Website>,
     #[builder(default)]
+    pub website_redirect: Option<RedirectAllRequestsTo>,
+    #[builder(default)]
     pub tags: Tags,
+    #[builder(default)]
+    pub versioning: Versioning,
+    #[builder(default)]
+    pub encryption: Option<Encryption>,
+    #[builder(default)]
+    pub public_access_block: Option<PublicAccessBlock>,
+    #[builder(default)]
+    pub object_ownership: Option<ObjectOwnership>,
+    #[builder(default)]
+    pub lifecycle_rules: Vec<LifecycleRule>,
+    #[builder(default)]
+    pub cors_rules: Vec<CorsRule>,
 }
 
 impl BucketBuilder {
     pub fn new(name: impl Into<String>) -> Self {
         BucketBuilder::default().name(name)
     }
+
+    fn validate(&self) -> Result<(), String> {
+        let public_acl = matches!(
+            self.acl,
+            Some(Acl::PublicRead) | Some(Acl::PublicReadWrite) | Some(Acl::AuthenticatedRead)
+        );
+        if let Some(Some(block)) = &self.public_access_block {
+            if public_acl && block.block_public_acls {
+                return Err("a public ACL can't be set while the public access block forbids it".into());
+            }
+        }
+
+        for rule in self.lifecycle_rules.iter().flatten() {
+            rule.validate()?;
+        }
+
+        for rule in self.cors_rules.iter().flatten() {
+            rule.validate()?;
+        }
+
+        match (&self.website, &self.website_redirect) {
+            (Some(Some(_)), Some(Some(_))) => Err(
+                "a bucket can either host a website or redirect all requests, not both".into(),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn client(provider: &AwsApi) -> Client {
+    let config = provider.details.s3_config();
+    Client::from_conf(config)
+}
+
+#[async_trait]
+impl Resource<Aws> for Bucket {
+    async fn read(&self, provider: &AwsApi, current: &Fields) -> Result<Fields, String> {
+        let client = client(provider);
+        let name = current.text("id").ok_or("no id recorded for the bucket")?;
+
+        info!("reading {}", name);
+        client
+            .head_bucket()
+            .bucket(name)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let acl = client
+            .get_bucket_acl()
+            .bucket(name)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let acl = Acl::from_grants(&acl.grants.unwrap_or_default());
+
+        let tags: Tags = match client.get_bucket_tagging().bucket(name).send().await {
+            Ok(output) => output
+                .tag_set
+                .unwrap_or_default()
+                .into_iter()
+                .map(|tag| (tag.key.unwrap_or_default(), tag.value.unwrap_or_default()))
+                .collect(),
+            Err(SdkError::ServiceError { err, .. }) if err.code() == Some("NoSuchTagSet") => {
+                Tags::new()
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let mut fields = Fields::empty()
+            .with_text("id", name)
+            .with_text("acl", acl.as_str());
+        fields = tags.record(fields);
+
+        let website = match client.get_bucket_website().bucket(name).send().await {
+            Ok(output) => Some(output),
+            Err(SdkError::ServiceError { err, .. })
+                if err.code() == Some("NoSuchWebsiteConfiguration") =>
+            {
+                None
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+
+        if let Some(website) = website {
+            if let Some(redirect) = website.redirect_all_requests_to {
+                fields = RedirectAllRequestsTo::from(redirect).record(fields);
+            } else {
+                fields = Website::from(website).record(fields);
+            }
+            fields = fields.with_text("website_endpoint", website_endpoint(name, &self.region));
+        }
+
+        let lifecycle_rules = match client
+            .get_bucket_lifecycle_configuration()
+            .bucket(name)
+            .send()
+            .await
+        {
+            Ok(output) => output.rules.unwrap_or_default(),
+            Err(SdkError::ServiceError { err, .. })
+                if err.code() == Some("NoSuchLifecycleConfiguration") =>
+            {
+                Vec::new()
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        fields = fields.with_array(
+            "lifecycle_rules",
+            lifecycle_rules
+                .into_iter()
+                .map(|rule| LifecycleRule::from(rule).fields()),
+        );
+
+        let cors_rules = match client.get_bucket_cors().bucket(name).send().await {
+            Ok(output) => output.cors_rules.unwrap_or_default(),
+            Err(SdkError::ServiceError { err, .. })
+                if err.code() == Some("NoSuchCORSConfiguration") =>
+            {
+                Vec::new()
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        fields = fields.with_array(
+            "cors_rules",
+            cors_rules
+                .into_iter()
+                .map(|rule| CorsRule::from(rule).fields()),
+        );
+
+        let versioning = client
+            .get_bucket_versioning()
+            .bucket(name)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        fields = fields.with_text(
+            "versioning",
+            Versioning::from_model(versioning.status).as_str(),
+        );
+
+        match client.get_bucket_encryption().bucket(name).send().await {
+            Ok(output) => {
+                let encryption = output
+                    .server_side_encryption_configuration
+                    .and_then(Encryption::from_model);
+                if let Some(encryption) = encryption {
+                    fields = encryption.record(fields);
+                }
+            }
+            Err(SdkError::ServiceError { err, .. })
+                if err.code() == Some("ServerSideEncryptionConfigurationNotFoundError") => {}
+            Err(e) => return Err(e.to_string()),
+        }
+
+        match client.get_public_access_block().bucket(name).send().await {
+            Ok(output) => {
+                if let Some(block) = output.public_access_block_configuration {
+                    fields = PublicAccessBlock::from(block).record(fields);
+                }
+            }
+            Err(SdkError::ServiceError { err, .. })
+                if err.code() == Some("NoSuchPublicAccessBlockConfiguration") => {}
+            Err(e) => return Err(e.to_string()),
+        }
+
+        match client.get_bucket_ownership_controls().bucket(name).send().await {
+            Ok(output) => {
+                let ownership = output
+                    .ownership_controls
+                    .and_then(|controls| controls.rules)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find_map(|rule| rule.object_ownership)
+                    .and_then(ObjectOwnership::from_model);
+                if let Some(ownership) = ownership {
+                    fields = fields.with_text("object_ownership", ownership.as_str());
+                }
+            }
+            Err(SdkError::ServiceError { err, .. })
+                if err.code() == Some("OwnershipControlsNotFoundError") => {}
+            Err(e) => return Err(e.to_string()),
+        }
+
+        Ok(fields)
+    }
+
+    fn drifted(&self, current: &Fields) -> bool {
+        !self.fields().diff(current).is_empty()
+    }
+
+    async fn update(&self, provider: &AwsApi, current: &Fields) -> Result<Fields, String> {
+        if current.text("id") != Some(self.name.as_str()) {
+            return Err(format!(
+                "renaming bucket {:?} to {:?} requires replacing it",
+                current.text("id"),
+                self.name
+            ));
+        }
+
+        let client = client(provider);
+        let desired = self.fields();
+
+        let changes = desired.diff(current);
+        let changed = |name: &str| changes.iter().any(|change| change.field_name() == name);
+
+        // Both of these can stand in the way of a new ACL, so they go first
+        if changed("object_ownership") {
+            info!("updating object ownership of {}", self.name);
+            self.apply_object_ownership(&client).await?;
+        }
+
+        if changed("public_access_block") {
+            info!("updating public access block of {}", self.name);
+            self.apply_public_access_block(&client).await?;
+        }
+
+        if changed("acl") {
+            info!("updating acl of {}", self.name);
+            self.apply_acl(&client).await?;
+        }
+
+        if changed("tags") {
+            info!("updating tags of {}", self.name);
+            self.apply_tags(&client).await?;
+        }
+
+        if changed("website") || changed("website_redirect") {
+            info!("updating website of {}", self.name);
+            self.apply_website(&client).await?;
+        }
+
+        if changed("encryption") {
+            info!("updating encryption of {}", self.name);
+            self.apply_encryption(&client).await?;
+        }
+
+        if changed("versioning") {
+            info!("updating versioning of {}", self.name);
+            self.apply_versioning(&client).await?;
+        }
+
+        if changed("lifecycle_rules") {
+            info!("updating lifecycle rules of {}", self.name);
+            self.apply_lifecycle_rules(&client).await?;
+        }
+
+        if changed("cors_rules") {
+            info!("updating CORS rules of {}", self.name);
+            self.apply_cors_rules(&client).await?;
+        }
+
+        Ok(desired)
+    }
+
+    async fn delete(&self, provider: &AwsApi, current: &Fields) -> Result<(), String> {
+        let client = client(provider);
+        let name = current.text("id").ok_or("no id recorded for the bucket")?;
+
+        info!("deleting {}", name);
+        client
+            .delete_bucket()
+            .bucket(name)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
 }
-impl Resource<Aws> for Bucket {}
 
 #[async_trait]
 impl Creatable<Aws> for Bucket {
-    async fn create(&self, provider: &AwsApi) -> Result<Fields, String> {
-        let config = provider.details.config();
-        let client = Client::from_conf(config);
+    async fn create(&self, provider: &AwsApi) -> Result<Fields, CreateError> {
+        let client = client(provider);
 
-        let request = client.create_bucket().bucket(&self.name);
+        let request = client
+            .create_bucket()
+            .bucket(&self.name)
+            .acl(self.acl.to_model())
+            .set_create_bucket_configuration(self.create_bucket_configuration());
         info!("creating {}", self.name);
         request.send().await.map_err(|e| e.to_string())?;
         info!("created {}", self.name);
 
-        let mut fields = Fields::empty().with_text("id", self.name.clone());
+        // The bucket exists from here on, even if configuring it fails
+        let created = Fields::empty().with_text("id", self.name.clone());
+        let partly = |message: String| CreateError::partly(created.clone(), message);
 
-        if let Some(website) = &self.website {
-            fields = fields.with_object("website", |o| {
-                o.with_text("index_document", &website.index_document)
-            });
+        if self.object_ownership.is_some() {
+            self.apply_object_ownership(&client).await.map_err(partly)?;
+        }
+        if self.public_access_block.is_some() {
+            self.apply_public_access_block(&client)
+                .await
+                .map_err(partly)?;
+        }
+        if self.encryption.is_some() {
+            self.apply_encryption(&client).await.map_err(partly)?;
+        }
+        if self.versioning != Versioning::Disabled {
+            self.apply_versioning(&client).await.map_err(partly)?;
+        }
+        if !self.lifecycle_rules.is_empty() {
+            self.apply_lifecycle_rules(&client).await.map_err(partly)?;
+        }
+        if !self.cors_rules.is_empty() {
+            self.apply_cors_rules(&client).await.map_err(partly)?;
+        }
+        if !self.tags.is_empty() {
+            self.apply_tags(&client).await.map_err(partly)?;
+        }
+        if self.website.is_some() || self.website_redirect.is_some() {
+            self.apply_website(&client).await.map_err(partly)?;
         }
 
-        Ok(fields)
+        Ok(self.fields())
     }
 
     fn kind(&self) -> &'static str {
@@ -69,69 +454,1420 @@ impl Creatable<Aws> for Bucket {
 
 impl Bucket {
     pub fn arn(&self) -> Arn<Bucket> {
-        ArnBuilder::default()
-            .partition("arn")
-            .service("s3")
-            .relative_id(self.name.clone())
-            .build()
-            .unwrap()
+        Arn::bucket(self.name.clone()).with_partition(Partition::for_region(&self.region))
     }
 
     pub fn name(&self) -> Value<String> {
         Value::Real(self.name.clone())
     }
+
+    /// Where the website hosted in this bucket can be reached.
+    /// Only meaningful if the bucket has a `website` or `website_redirect`.
+    pub fn website_endpoint(&self) -> Value<String> {
+        Value::Real(website_endpoint(&self.name, &self.region))
+    }
+
+    /// S3 creates the bucket in us-east-1 unless it is told where,
+    /// and rejects being told us-east-1.
+    fn create_bucket_configuration(&self) -> Option<model::CreateBucketConfiguration> {
+        if self.region == "us-east-1" {
+            return None;
+        }
+
+        Some(
+            model::CreateBucketConfiguration::builder()
+                .location_constraint(model::BucketLocationConstraint::from(self.region.as_str()))
+                .build(),
+        )
+    }
+
+    /// The fields we expect to see once the bucket matches its definition.
+    fn fields(&self) -> Fields {
+        let mut fields = Fields::empty()
+            .with_text("id", self.name.clone())
+            .with_text("acl", self.acl.as_str());
+        fields = self.tags.record(fields);
+
+        if let Some(website) = &self.website {
+            fields = website.record(fields);
+        }
+
+        if let Some(redirect) = &self.website_redirect {
+            fields = redirect.record(fields);
+        }
+
+        if self.website.is_some() || self.website_redirect.is_some() {
+            fields = fields.with_text("website_endpoint", website_endpoint(&self.name, &self.region));
+        }
+
+        fields = fields.with_text("versioning", self.versioning.as_str());
+
+        if let Some(encryption) = &self.encryption {
+            fields = encryption.record(fields);
+        }
+
+        if let Some(block) = &self.public_access_block {
+            fields = block.record(fields);
+        }
+
+        if let Some(ownership) = &self.object_ownership {
+            fields = fields.with_text("object_ownership", ownership.as_str());
+        }
+
+        fields
+            .with_array(
+                "lifecycle_rules",
+                self.lifecycle_rules.iter().map(LifecycleRule::fields),
+            )
+            .with_array("cors_rules", self.cors_rules.iter().map(CorsRule::fields))
+    }
+
+    async fn apply_acl(&self, client: &Client) -> Result<(), String> {
+        client
+            .put_bucket_acl()
+            .bucket(&self.name)
+            .acl(self.acl.to_model())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    async fn apply_tags(&self, client: &Client) -> Result<(), String> {
+        if self.tags.is_empty() {
+            client
+                .delete_bucket_tagging()
+                .bucket(&self.name)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            return Ok(());
+        }
+
+        let tagging = self
+            .tags
+            .iter()
+            .fold(model::Tagging::builder(), |tagging, (key, value)| {
+                tagging.tag_set(model::Tag::builder().key(key).value(value).build())
+            })
+            .build();
+
+        client
+            .put_bucket_tagging()
+            .bucket(&self.name)
+            .tagging(tagging)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    async fn apply_website(&self, client: &Client) -> Result<(), String> {
+        let configuration = match (&self.website, &self.website_redirect) {
+            (Some(website), _) => Some(website.to_configuration()),
+            (None, Some(redirect)) => Some(
+                model::WebsiteConfiguration::builder()
+                    .redirect_all_requests_to(redirect.to_model())
+                    .build(),
+            ),
+            (None, None) => None,
+        };
+
+        match configuration {
+            Some(configuration) => {
+                info!("configuring website for {}", self.name);
+                client
+                    .put_bucket_website()
+                    .bucket(&self.name)
+                    .website_configuration(configuration)
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            // Only on update, once a website that was recorded is gone
+            None => {
+                client
+                    .delete_bucket_website()
+                    .bucket(&self.name)
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn apply_versioning(&self, client: &Client) -> Result<(), String> {
+        let status = self.versioning.to_model().ok_or_else(|| {
+            format!(
+                "versioning of {} can't be disabled once it has been enabled, suspend it instead",
+                self.name
+            )
+        })?;
+
+        client
+            .put_bucket_versioning()
+            .bucket(&self.name)
+            .versioning_configuration(
+                model::VersioningConfiguration::builder()
+                    .status(status)
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    async fn apply_encryption(&self, client: &Client) -> Result<(), String> {
+        match &self.encryption {
+            Some(encryption) => {
+                client
+                    .put_bucket_encryption()
+                    .bucket(&self.name)
+                    .server_side_encryption_configuration(encryption.to_model())
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            None => {
+                client
+                    .delete_bucket_encryption()
+                    .bucket(&self.name)
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn apply_public_access_block(&self, client: &Client) -> Result<(), String> {
+        match &self.public_access_block {
+            Some(block) => {
+                client
+                    .put_public_access_block()
+                    .bucket(&self.name)
+                    .public_access_block_configuration(block.to_model())
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            None => {
+                client
+                    .delete_public_access_block()
+                    .bucket(&self.name)
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn apply_lifecycle_rules(&self, client: &Client) -> Result<(), String> {
+        if self.lifecycle_rules.is_empty() {
+            client
+                .delete_bucket_lifecycle()
+                .bucket(&self.name)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            return Ok(());
+        }
+
+        let configuration = self
+            .lifecycle_rules
+            .iter()
+            .fold(
+                model::BucketLifecycleConfiguration::builder(),
+                |configuration, rule| configuration.rules(rule.to_model()),
+            )
+            .build();
+
+        client
+            .put_bucket_lifecycle_configuration()
+            .bucket(&self.name)
+            .lifecycle_configuration(configuration)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    async fn apply_cors_rules(&self, client: &Client) -> Result<(), String> {
+        if self.cors_rules.is_empty() {
+            client
+                .delete_bucket_cors()
+                .bucket(&self.name)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            return Ok(());
+        }
+
+        let configuration = self
+            .cors_rules
+            .iter()
+            .fold(model::CorsConfiguration::builder(), |configuration, rule| {
+                configuration.cors_rules(rule.to_model())
+            })
+            .build();
+
+        client
+            .put_bucket_cors()
+            .bucket(&self.name)
+            .cors_configuration(configuration)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    async fn apply_object_ownership(&self, client: &Client) -> Result<(), String> {
+        match &self.object_ownership {
+            Some(ownership) => {
+                let controls = model::OwnershipControls::builder()
+                    .rules(
+                        model::OwnershipControlsRule::builder()
+                            .object_ownership(ownership.to_model())
+                            .build(),
+                    )
+                    .build();
+
+                client
+                    .put_bucket_ownership_controls()
+                    .bucket(&self.name)
+                    .ownership_controls(controls)
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            None => {
+                client
+                    .delete_bucket_ownership_controls()
+                    .bucket(&self.name)
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
-#[derive(Debug, Clone)]
+/// The regions that still use the older `s3-website-<region>` endpoints
+/// instead of `s3-website.<region>`.
+const LEGACY_WEBSITE_REGIONS: &[&str] = &[
+    "us-east-1",
+    "us-west-1",
+    "us-west-2",
+    "us-gov-west-1",
+    "eu-west-1",
+    "ap-southeast-1",
+    "ap-southeast-2",
+    "ap-northeast-1",
+    "sa-east-1",
+];
+
+fn website_endpoint(bucket: &str, region: &str) -> String {
+    if LEGACY_WEBSITE_REGIONS.contains(&region) {
+        format!("http://{}.s3-website-{}.amazonaws.com", bucket, region)
+    } else {
+        format!("http://{}.s3-website.{}.amazonaws.com", bucket, region)
+    }
+}
+
+/// Serve the content of the bucket as a static website.
+#[derive(Debug, Clone, Default)]
 pub struct Website {
     pub index_document: String,
+    pub error_document: Option<String>,
+    pub routing_rules: Vec<RoutingRule>,
+}
+
+impl Website {
+    fn record(&self, fields: Fields) -> Fields {
+        fields.with_object("website", |o| {
+            let mut o = o
+                .with_text("index_document", &self.index_document)
+                .with_array(
+                    "routing_rules",
+                    self.routing_rules.iter().map(RoutingRule::fields),
+                );
+            if let Some(error_document) = &self.error_document {
+                o = o.with_text("error_document", error_document);
+            }
+            o
+        })
+    }
+
+    fn to_configuration(&self) -> model::WebsiteConfiguration {
+        let mut configuration = model::WebsiteConfiguration::builder().index_document(
+            model::IndexDocument::builder()
+                .suffix(&self.index_document)
+                .build(),
+        );
+
+        if let Some(error_document) = &self.error_document {
+            configuration = configuration
+                .error_document(model::ErrorDocument::builder().key(error_document).build());
+        }
+
+        for rule in &self.routing_rules {
+            configuration = configuration.routing_rules(rule.to_model());
+        }
+
+        configuration.build()
+    }
+}
+
+impl From<aws_sdk_s3::output::GetBucketWebsiteOutput> for Website {
+    fn from(output: aws_sdk_s3::output::GetBucketWebsiteOutput) -> Self {
+        Website {
+            index_document: output
+                .index_document
+                .and_then(|index| index.suffix)
+                .unwrap_or_default(),
+            error_document: output.error_document.and_then(|error| error.key),
+            routing_rules: output
+                .routing_rules
+                .unwrap_or_default()
+                .into_iter()
+                .map(RoutingRule::from)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Http,
+    Https,
+}
+
+impl Protocol {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Protocol::Http => "http",
+            Protocol::Https => "https",
+        }
+    }
+
+    fn to_model(self) -> model::Protocol {
+        match self {
+            Protocol::Http => model::Protocol::Http,
+            Protocol::Https => model::Protocol::Https,
+        }
+    }
+
+    fn from_model(protocol: model::Protocol) -> Option<Self> {
+        match protocol {
+            model::Protocol::Http => Some(Protocol::Http),
+            model::Protocol::Https => Some(Protocol::Https),
+            _ => None,
+        }
+    }
+}
+
+/// Redirect every request made to the bucket's website to another host.
+#[derive(Debug, Clone)]
+pub struct RedirectAllRequestsTo {
+    pub host_name: String,
+    pub protocol: Option<Protocol>,
+}
+
+impl RedirectAllRequestsTo {
+    fn record(&self, fields: Fields) -> Fields {
+        fields.with_object("website_redirect", |o| {
+            let o = o.with_text("host_name", &self.host_name);
+            match self.protocol {
+                Some(protocol) => o.with_text("protocol", protocol.as_str()),
+                None => o,
+            }
+        })
+    }
+
+    fn to_model(&self) -> model::RedirectAllRequestsTo {
+        let mut redirect = model::RedirectAllRequestsTo::builder().host_name(&self.host_name);
+        if let Some(protocol) = self.protocol {
+            redirect = redirect.protocol(protocol.to_model());
+        }
+        redirect.build()
+    }
+}
+
+impl From<model::RedirectAllRequestsTo> for RedirectAllRequestsTo {
+    fn from(redirect: model::RedirectAllRequestsTo) -> Self {
+        RedirectAllRequestsTo {
+            host_name: redirect.host_name.unwrap_or_default(),
+            protocol: redirect.protocol.and_then(Protocol::from_model),
+        }
+    }
+}
+
+/// Redirects requests that match the `condition`.
+/// Without a condition, all requests are redirected.
+#[derive(Debug, Clone, Default)]
+pub struct RoutingRule {
+    pub condition: Option<RoutingRuleCondition>,
+    pub redirect: Redirect,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RoutingRuleCondition {
+    pub key_prefix_equals: Option<String>,
+    pub http_error_code_returned_equals: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Redirect {
+    pub host_name: Option<String>,
+    pub http_redirect_code: Option<String>,
+    pub protocol: Option<Protocol>,
+    pub replace_key_prefix_with: Option<String>,
+    pub replace_key_with: Option<String>,
+}
+
+impl RoutingRule {
+    fn fields(&self) -> Fields {
+        let mut fields = Fields::empty();
+
+        if let Some(condition) = &self.condition {
+            let mut condition_fields = Fields::empty();
+            if let Some(prefix) = &condition.key_prefix_equals {
+                condition_fields = condition_fields.with_text("key_prefix_equals", prefix);
+            }
+            if let Some(code) = &condition.http_error_code_returned_equals {
+                condition_fields =
+                    condition_fields.with_text("http_error_code_returned_equals", code);
+            }
+            fields = fields.with_object("condition", |_| condition_fields.clone());
+        }
+
+        let redirect = &self.redirect;
+        let mut redirect_fields = Fields::empty();
+        let texts = [
+            ("host_name", &redirect.host_name),
+            ("http_redirect_code", &redirect.http_redirect_code),
+            ("replace_key_prefix_with", &redirect.replace_key_prefix_with),
+            ("replace_key_with", &redirect.replace_key_with),
+        ];
+        for (name, value) in texts {
+            if let Some(value) = value {
+                redirect_fields = redirect_fields.with_text(name, value);
+            }
+        }
+        if let Some(protocol) = redirect.protocol {
+            redirect_fields = redirect_fields.with_text("protocol", protocol.as_str());
+        }
+
+        fields.with_object("redirect", |_| redirect_fields.clone())
+    }
+
+    fn to_model(&self) -> model::RoutingRule {
+        let mut rule = model::RoutingRule::builder();
+
+        if let Some(condition) = &self.condition {
+            let mut c = model::Condition::builder();
+            if let Some(prefix) = &condition.key_prefix_equals {
+                c = c.key_prefix_equals(prefix);
+            }
+            if let Some(code) = &condition.http_error_code_returned_equals {
+                c = c.http_error_code_returned_equals(code);
+            }
+            rule = rule.condition(c.build());
+        }
+
+        let redirect = &self.redirect;
+        let mut r = model::Redirect::builder();
+        if let Some(host_name) = &redirect.host_name {
+            r = r.host_name(host_name);
+        }
+        if let Some(code) = &redirect.http_redirect_code {
+            r = r.http_redirect_code(code);
+        }
+        if let Some(protocol) = redirect.protocol {
+            r = r.protocol(protocol.to_model());
+        }
+        if let Some(prefix) = &redirect.replace_key_prefix_with {
+            r = r.replace_key_prefix_with(prefix);
+        }
+        if let Some(key) = &redirect.replace_key_with {
+            r = r.replace_key_with(key);
+        }
+
+        rule.redirect(r.build()).build()
+    }
+}
+
+impl From<model::RoutingRule> for RoutingRule {
+    fn from(rule: model::RoutingRule) -> Self {
+        let redirect = rule.redirect.unwrap_or_else(|| model::Redirect::builder().build());
+
+        RoutingRule {
+            condition: rule.condition.map(|condition| RoutingRuleCondition {
+                key_prefix_equals: condition.key_prefix_equals,
+                http_error_code_returned_equals: condition.http_error_code_returned_equals,
+            }),
+            redirect: Redirect {
+                host_name: redirect.host_name,
+                http_redirect_code: redirect.http_redirect_code,
+                protocol: redirect.protocol.and_then(Protocol::from_model),
+                replace_key_prefix_with: redirect.replace_key_prefix_with,
+                replace_key_with: redirect.replace_key_with,
+            },
+        }
+    }
+}
+
+/// Whether S3 keeps every version of the objects in a bucket.
+/// Once enabled, versioning can only be suspended, never disabled again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Versioning {
+    Disabled,
+    Enabled,
+    Suspended,
+}
+
+// Deriving it needs `#[default]` on the variant, which needs Rust 1.62
+#[allow(clippy::derivable_impls)]
+impl Default for Versioning {
+    fn default() -> Self {
+        Versioning::Disabled
+    }
+}
+
+impl Versioning {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Versioning::Disabled => "disabled",
+            Versioning::Enabled => "enabled",
+            Versioning::Suspended => "suspended",
+        }
+    }
+
+    fn to_model(self) -> Option<model::BucketVersioningStatus> {
+        match self {
+            Versioning::Disabled => None,
+            Versioning::Enabled => Some(model::BucketVersioningStatus::Enabled),
+            Versioning::Suspended => Some(model::BucketVersioningStatus::Suspended),
+        }
+    }
+
+    /// Buckets that never had versioning enabled don't report a status at all.
+    fn from_model(status: Option<model::BucketVersioningStatus>) -> Self {
+        match status {
+            Some(model::BucketVersioningStatus::Enabled) => Versioning::Enabled,
+            Some(model::BucketVersioningStatus::Suspended) => Versioning::Suspended,
+            _ => Versioning::Disabled,
+        }
+    }
+}
+
+/// How objects are encrypted at rest when they don't ask for anything else.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Encryption {
+    /// SSE-S3, with keys managed by S3
+    S3,
+    /// SSE-KMS, with the AWS managed key unless a `key_id` is given.
+    /// A bucket key cuts down on the requests made to KMS.
+    Kms {
+        key_id: Option<String>,
+        bucket_key: bool,
+    },
+}
+
+impl Encryption {
+    fn record(&self, fields: Fields) -> Fields {
+        fields.with_object("encryption", |o| match self {
+            Encryption::S3 => o.with_text("algorithm", "AES256"),
+            Encryption::Kms { key_id, bucket_key } => {
+                let o = o
+                    .with_text("algorithm", "aws:kms")
+                    .with_boolean("bucket_key", *bucket_key);
+                match key_id {
+                    Some(key_id) => o.with_text("kms_key_id", key_id),
+                    None => o,
+                }
+            }
+        })
+    }
+
+    fn to_model(&self) -> model::ServerSideEncryptionConfiguration {
+        let (algorithm, key_id, bucket_key) = match self {
+            Encryption::S3 => (model::ServerSideEncryption::Aes256, None, false),
+            Encryption::Kms { key_id, bucket_key } => {
+                (model::ServerSideEncryption::AwsKms, key_id.clone(), *bucket_key)
+            }
+        };
+
+        let default = model::ServerSideEncryptionByDefault::builder()
+            .sse_algorithm(algorithm)
+            .set_kms_master_key_id(key_id)
+            .build();
+
+        model::ServerSideEncryptionConfiguration::builder()
+            .rules(
+                model::ServerSideEncryptionRule::builder()
+                    .apply_server_side_encryption_by_default(default)
+                    .bucket_key_enabled(bucket_key)
+                    .build(),
+            )
+            .build()
+    }
+
+    fn from_model(configuration: model::ServerSideEncryptionConfiguration) -> Option<Self> {
+        let rule = configuration.rules.unwrap_or_default().into_iter().next()?;
+        let default = rule.apply_server_side_encryption_by_default?;
+
+        match default.sse_algorithm? {
+            model::ServerSideEncryption::Aes256 => Some(Encryption::S3),
+            model::ServerSideEncryption::AwsKms => Some(Encryption::Kms {
+                key_id: default.kms_master_key_id,
+                bucket_key: rule.bucket_key_enabled,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Guards against a bucket or its objects becoming public by accident.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PublicAccessBlock {
+    pub block_public_acls: bool,
+    pub ignore_public_acls: bool,
+    pub block_public_policy: bool,
+    pub restrict_public_buckets: bool,
+}
+
+impl PublicAccessBlock {
+    /// Blocks every way of making the bucket public, as our baseline requires
+    pub fn all() -> Self {
+        PublicAccessBlock {
+            block_public_acls: true,
+            ignore_public_acls: true,
+            block_public_policy: true,
+            restrict_public_buckets: true,
+        }
+    }
+
+    fn record(&self, fields: Fields) -> Fields {
+        fields.with_object("public_access_block", |o| {
+            o.with_boolean("block_public_acls", self.block_public_acls)
+                .with_boolean("ignore_public_acls", self.ignore_public_acls)
+                .with_boolean("block_public_policy", self.block_public_policy)
+                .with_boolean("restrict_public_buckets", self.restrict_public_buckets)
+        })
+    }
+
+    fn to_model(self) -> model::PublicAccessBlockConfiguration {
+        model::PublicAccessBlockConfiguration::builder()
+            .block_public_acls(self.block_public_acls)
+            .ignore_public_acls(self.ignore_public_acls)
+            .block_public_policy(self.block_public_policy)
+            .restrict_public_buckets(self.restrict_public_buckets)
+            .build()
+    }
+}
+
+impl From<model::PublicAccessBlockConfiguration> for PublicAccessBlock {
+    fn from(block: model::PublicAccessBlockConfiguration) -> Self {
+        PublicAccessBlock {
+            block_public_acls: block.block_public_acls,
+            ignore_public_acls: block.ignore_public_acls,
+            block_public_policy: block.block_public_policy,
+            restrict_public_buckets: block.restrict_public_buckets,
+        }
+    }
+}
+
+/// Who owns objects that other accounts upload into the bucket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectOwnership {
+    /// The bucket owner, as long as the upload grants `bucket-owner-full-control`
+    BucketOwnerPreferred,
+    /// The account that uploaded the object
+    ObjectWriter,
+}
+
+impl ObjectOwnership {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ObjectOwnership::BucketOwnerPreferred => "BucketOwnerPreferred",
+            ObjectOwnership::ObjectWriter => "ObjectWriter",
+        }
+    }
+
+    fn to_model(self) -> model::ObjectOwnership {
+        match self {
+            ObjectOwnership::BucketOwnerPreferred => model::ObjectOwnership::BucketOwnerPreferred,
+            ObjectOwnership::ObjectWriter => model::ObjectOwnership::ObjectWriter,
+        }
+    }
+
+    fn from_model(ownership: model::ObjectOwnership) -> Option<Self> {
+        match ownership {
+            model::ObjectOwnership::BucketOwnerPreferred => {
+                Some(ObjectOwnership::BucketOwnerPreferred)
+            }
+            model::ObjectOwnership::ObjectWriter => Some(ObjectOwnership::ObjectWriter),
+            _ => None,
+        }
+    }
+}
+
+/// Where S3 keeps an object, trading retrieval cost and latency for storage cost.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StorageClass {
+    Standard,
+    ReducedRedundancy,
+    StandardIa,
+    OnezoneIa,
+    IntelligentTiering,
+    Glacier,
+    DeepArchive,
+}
+
+impl StorageClass {
+    fn as_str(&self) -> &'static str {
+        match self {
+            StorageClass::Standard => "STANDARD",
+            StorageClass::ReducedRedundancy => "REDUCED_REDUNDANCY",
+            StorageClass::StandardIa => "STANDARD_IA",
+            StorageClass::OnezoneIa => "ONEZONE_IA",
+            StorageClass::IntelligentTiering => "INTELLIGENT_TIERING",
+            StorageClass::Glacier => "GLACIER",
+            StorageClass::DeepArchive => "DEEP_ARCHIVE",
+        }
+    }
+
+    fn to_model(self) -> model::StorageClass {
+        match self {
+            StorageClass::Standard => model::StorageClass::Standard,
+            StorageClass::ReducedRedundancy => model::StorageClass::ReducedRedundancy,
+            StorageClass::StandardIa => model::StorageClass::StandardIa,
+            StorageClass::OnezoneIa => model::StorageClass::OnezoneIa,
+            StorageClass::IntelligentTiering => model::StorageClass::IntelligentTiering,
+            StorageClass::Glacier => model::StorageClass::Glacier,
+            StorageClass::DeepArchive => model::StorageClass::DeepArchive,
+        }
+    }
+
+    fn from_model(class: model::StorageClass) -> Option<Self> {
+        match class {
+            model::StorageClass::Standard => Some(StorageClass::Standard),
+            model::StorageClass::ReducedRedundancy => Some(StorageClass::ReducedRedundancy),
+            model::StorageClass::StandardIa => Some(StorageClass::StandardIa),
+            model::StorageClass::OnezoneIa => Some(StorageClass::OnezoneIa),
+            model::StorageClass::IntelligentTiering => Some(StorageClass::IntelligentTiering),
+            model::StorageClass::Glacier => Some(StorageClass::Glacier),
+            model::StorageClass::DeepArchive => Some(StorageClass::DeepArchive),
+            _ => None,
+        }
+    }
+
+    /// Objects can't be transitioned back to the standard classes
+    fn to_transition_model(self) -> Option<model::TransitionStorageClass> {
+        match self {
+            StorageClass::Standard | StorageClass::ReducedRedundancy => None,
+            StorageClass::StandardIa => Some(model::TransitionStorageClass::StandardIa),
+            StorageClass::OnezoneIa => Some(model::TransitionStorageClass::OnezoneIa),
+            StorageClass::IntelligentTiering => {
+                Some(model::TransitionStorageClass::IntelligentTiering)
+            }
+            StorageClass::Glacier => Some(model::TransitionStorageClass::Glacier),
+            StorageClass::DeepArchive => Some(model::TransitionStorageClass::DeepArchive),
+        }
+    }
+
+    fn from_transition_model(class: model::TransitionStorageClass) -> Option<Self> {
+        match class {
+            model::TransitionStorageClass::StandardIa => Some(StorageClass::StandardIa),
+            model::TransitionStorageClass::OnezoneIa => Some(StorageClass::OnezoneIa),
+            model::TransitionStorageClass::IntelligentTiering => {
+                Some(StorageClass::IntelligentTiering)
+            }
+            model::TransitionStorageClass::Glacier => Some(StorageClass::Glacier),
+            model::TransitionStorageClass::DeepArchive => Some(StorageClass::DeepArchive),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Builder, Clone, Debug)]
 #[builder(setter(strip_option, into), pattern = "owned")]
-pub struct BucketObject {
+pub struct BucketPolicy {
     bucket: Value<String>,
-    key: String,
-    content_type: String,
-    content: String,
+    policy: PolicyDocument,
 }
 
-impl BucketObjectBuilder {
+impl BucketPolicyBuilder {
     pub fn new() -> Self {
-        BucketObjectBuilder::default()
+        BucketPolicyBuilder::default()
+    }
+
+    /// Attach the policy to a bucket defined in the same program,
+    /// e.g. by passing the `Meta<Bucket>` returned from the provider.
+    pub fn bucket_of(self, bucket: &Bucket) -> Self {
+        self.bucket(bucket.name())
+    }
+}
+
+impl BucketPolicy {
+    fn fields(&self, bucket_name: &str) -> Fields {
+        Fields::empty()
+            .with_text("bucket", bucket_name)
+            .with_text("policy", self.policy.to_json())
     }
 }
 
-impl Resource<Aws> for BucketObject {}
+/// AWS hands back the policy with its own formatting and key order,
+/// so it is rendered again the way `fields` renders it before it is compared.
+fn read_policy(bucket_name: &str, policy: &str) -> Result<Fields, String> {
+    let policy = PolicyDocument::from_json(policy)
+        .map_err(|e| format!("policy of {} is not a policy document: {}", bucket_name, e))?;
+
+    Ok(Fields::empty()
+        .with_text("bucket", bucket_name)
+        .with_text("policy", policy.to_json()))
+}
 
 #[async_trait]
-impl Creatable<Aws> for BucketObject {
-    async fn create(&self, provider: &AwsApi) -> Result<Fields, String> {
-        let config = provider.details.config();
-        let client = Client::from_conf(config);
+impl Resource<Aws> for BucketPolicy {
+    async fn read(&self, provider: &AwsApi, current: &Fields) -> Result<Fields, String> {
+        let client = client(provider);
+        let bucket_name = current.text("bucket").ok_or("no bucket recorded for the policy")?;
+
+        info!("reading policy of {}", bucket_name);
+        let output = client
+            .get_bucket_policy()
+            .bucket(bucket_name)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
 
+        read_policy(bucket_name, &output.policy.unwrap_or_default())
+    }
+
+    async fn update(&self, provider: &AwsApi, current: &Fields) -> Result<Fields, String> {
         let bucket_name = self.bucket.get();
 
-        let request = client
-            .put_object()
-            .bucket(&bucket_name)
-            .key(&self.key)
-            .content_type(&self.content_type)
-            .body(ByteStream::from(self.content.clone().into_bytes()));
+        if current.text("bucket") != Some(bucket_name.as_str()) {
+            // The policy moved to a different bucket, so the old one has to go
+            self.delete(provider, current).await?;
+        }
 
-        info!("creating object for {}/{}", &bucket_name, &self.key);
-        request.send().await.map_err(|e| e.to_string())?;
+        Ok(self.create(provider).await?)
+    }
 
-        Ok(Fields::empty())
+    async fn delete(&self, provider: &AwsApi, current: &Fields) -> Result<(), String> {
+        let client = client(provider);
+        let bucket_name = current.text("bucket").ok_or("no bucket recorded for the policy")?;
+
+        info!("deleting policy of {}", bucket_name);
+        client
+            .delete_bucket_policy()
+            .bucket(bucket_name)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Creatable<Aws> for BucketPolicy {
+    async fn create(&self, provider: &AwsApi) -> Result<Fields, CreateError> {
+        let client = client(provider);
+        let bucket_name = self.bucket.get();
+
+        info!("attaching policy to {}", bucket_name);
+        client
+            .put_bucket_policy()
+            .bucket(&bucket_name)
+            .policy(self.policy.to_json())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(self.fields(&bucket_name))
     }
 
     fn kind(&self) -> &'static str {
-        "s3_bucket_object"
+        "s3_bucket_policy"
     }
 }
 
-#[derive(Debug)]
-pub struct BucketPolicy {
-    pub bucket: Rc<Bucket>,
-    pub policy: Rc<PolicyDocument>,
+impl Arn<Bucket> {
+    /// `arn:aws:s3:::<name>`, the name may contain wildcards
+    pub fn bucket<S: Into<String>>(name: S) -> Self {
+        ArnBuilder::default()
+            .service(Bucket::SERVICE)
+            .relative_id(name)
+            .build()
+            .unwrap()
+    }
+
+    pub fn bucket_name(&self) -> &str {
+        self.relative_id()
+    }
+
+    /// `arn:aws:s3:::<bucket>/<key>`, the key may contain wildcards
+    pub fn object<S: AsRef<str>>(&self, key: S) -> Arn<BucketObject> {
+        ArnBuilder::default()
+            .partition(self.partition())
+            .service(Bucket::SERVICE)
+            .relative_id(format!("{}/{}", self.bucket_name(), key.as_ref()))
+            .build()
+            .unwrap()
+    }
+
+    /// Matches every object in the bucket, as is common in bucket policies
+    pub fn all_objects(&self) -> Arn<BucketObject> {
+        self.object("*")
+    }
+}
+
+impl Arn<BucketObject> {
+    pub fn bucket_name(&self) -> &str {
+        self.relative_id()
+            .split_once('/')
+            .map(|(bucket, _)| bucket)
+            .unwrap_or_default()
+    }
+
+    pub fn key(&self) -> &str {
+        self.relative_id()
+            .split_once('/')
+            .map(|(_, key)| key)
+            .unwrap_or_default()
+    }
+}
+
+/// Checks a bucket name against the S3 naming rules.
+/// Names with wildcards are patterns, so only their characters are checked.
+fn validate_bucket_name(name: &str) -> Result<(), String> {
+    let allowed = |c: char| {
+        c.is_ascii_lowercase() || c.is_ascii_digit() || c == '.' || c == '-' || c == '*' || c == '?'
+    };
+
+    if let Some(c) = name.chars().find(|c| !allowed(*c)) {
+        return Err(format!("`{}` is not allowed in bucket names", c));
+    }
+
+    if name.contains(['*', '?']) {
+        return Ok(());
+    }
+
+    if name.len() < 3 || name.len() > 63 {
+        return Err("bucket names are between 3 and 63 characters long".into());
+    }
+
+    let edges = [name.chars().next(), name.chars().last()];
+    if edges
+        .iter()
+        .flatten()
+        .any(|c| !c.is_ascii_alphanumeric())
+    {
+        return Err("bucket names start and end with a letter or digit".into());
+    }
+
+    if name.contains("..") {
+        return Err("bucket names can't contain two adjacent periods".into());
+    }
+
+    Ok(())
+}
+
+impl ArnKind for Bucket {
+    const SERVICE: &'static str = "s3";
+
+    fn validate(arn: &Arn<Self>) -> Result<(), ArnError> {
+        arn.expect_global()?;
+
+        validate_bucket_name(arn.relative_id()).map_err(|reason| ArnError::InvalidResource {
+            kind: "bucket",
+            resource: arn.relative_id().to_string(),
+            reason,
+        })
+    }
+}
+
+impl ArnKind for BucketObject {
+    const SERVICE: &'static str = "s3";
+
+    fn validate(arn: &Arn<Self>) -> Result<(), ArnError> {
+        arn.expect_global()?;
+
+        let invalid = |reason: String| ArnError::InvalidResource {
+            kind: "object",
+            resource: arn.relative_id().to_string(),
+            reason,
+        };
+
+        match arn.relative_id().split_once('/') {
+            Some((bucket, key)) if !key.is_empty() => {
+                validate_bucket_name(bucket).map_err(invalid)
+            }
+            _ => Err(invalid("objects look like `<bucket>/<key>`".into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn website_endpoints_depend_on_the_region() {
+        assert_eq!(
+            website_endpoint("my-site", "us-east-1"),
+            "http://my-site.s3-website-us-east-1.amazonaws.com"
+        );
+        assert_eq!(
+            website_endpoint("my-site", "eu-central-1"),
+            "http://my-site.s3-website.eu-central-1.amazonaws.com"
+        );
+    }
+
+    #[test]
+    fn buckets_cannot_host_and_redirect_at_the_same_time() {
+        let bucket = BucketBuilder::new("my-site")
+            .website(Website {
+                index_document: "index.html".into(),
+                ..Default::default()
+            })
+            .website_redirect(RedirectAllRequestsTo {
+                host_name: "example.com".into(),
+                protocol: Some(Protocol::Https),
+            })
+            .build();
+
+        assert!(bucket.is_err());
+    }
+
+    #[test]
+    fn acl_and_tags_are_part_of_the_fields() {
+        let bucket = BucketBuilder::new("my-site")
+            .acl(Acl::PublicRead)
+            .tags(Tags::new().with("kind", "demo"))
+            .build()
+            .unwrap();
+
+        let fields = bucket.fields();
+
+        assert_eq!(fields.text("acl"), Some("public-read"));
+        assert_eq!(
+            fields.object("tags").and_then(|tags| tags.text("kind")),
+            Some("demo")
+        );
+
+        let retagged = BucketBuilder::new("my-site")
+            .acl(Acl::PublicRead)
+            .tags(Tags::new().with("kind", "production"))
+            .build()
+            .unwrap();
+
+        let changes = retagged.fields().diff(&fields);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].field_name(), "tags");
+    }
+
+    #[test]
+    fn bucket_policies_record_the_rendered_document() {
+        use crate::iam::{Action, Principal, PolicyStatementBuilder};
+
+        let bucket = BucketBuilder::new("my-site").build().unwrap();
+        let statement = PolicyStatementBuilder::default()
+            .principal(Principal::AWS("*".into()))
+            .action(Action::new("s3:GetObject"))
+            .resource(crate::iam::Resource::new("arn:aws:s3:::my-site/*"))
+            .build()
+            .unwrap();
+
+        let policy = BucketPolicyBuilder::new()
+            .bucket_of(&bucket)
+            .policy(PolicyDocument::new(vec![statement]))
+            .build()
+            .unwrap();
+
+        let fields = policy.fields("my-site");
+        let rendered: serde_json::Value =
+            serde_json::from_str(fields.text("policy").unwrap()).unwrap();
+
+        assert_eq!(fields.text("bucket"), Some("my-site"));
+        assert_eq!(rendered["Version"], "2012-10-17");
+        assert_eq!(rendered["Statement"][0]["Principal"]["AWS"], "*");
+        assert_eq!(rendered["Statement"][0]["Action"], "s3:GetObject");
+    }
+
+    #[test]
+    fn bucket_policies_read_back_as_they_were_created() {
+        use crate::iam::{Action, PolicyStatementBuilder, Principal};
+
+        let statement = PolicyStatementBuilder::default()
+            .sid("PublicReadGetObject".to_string())
+            .principal(Principal::AWS("*".into()))
+            .action(Action::new("s3:GetObject"))
+            .resource(crate::iam::Resource::new("arn:aws:s3:::my-site/*"))
+            .build()
+            .unwrap();
+        let policy = BucketPolicyBuilder::new()
+            .bucket("my-site")
+            .policy(PolicyDocument::new(vec![statement]))
+            .build()
+            .unwrap();
+
+        // The way S3 returns it, without whitespace and in its own order
+        let returned = r#"{"Statement":[{"Resource":"arn:aws:s3:::my-site/*","Action":"s3:GetObject","Principal":{"AWS":"*"},"Effect":"Allow","Sid":"PublicReadGetObject"}],"Version":"2012-10-17"}"#;
+
+        assert_eq!(
+            read_policy("my-site", returned).unwrap(),
+            policy.fields("my-site")
+        );
+    }
+
+    #[test]
+    fn public_read_is_recognised_from_its_grants() {
+        let all_users_can_read = model::Grant::builder()
+            .grantee(model::Grantee::builder().uri(ALL_USERS).build())
+            .permission(model::Permission::Read)
+            .build();
+
+        assert_eq!(Acl::from_grants(&[]), Acl::Private);
+        assert_eq!(Acl::from_grants(&[all_users_can_read]), Acl::PublicRead);
+    }
+
+    #[test]
+    fn compliance_settings_are_part_of_the_fields() {
+        let bucket = BucketBuilder::new("my-logs")
+            .versioning(Versioning::Enabled)
+            .encryption(Encryption::Kms {
+                key_id: Some("alias/logs".into()),
+                bucket_key: true,
+            })
+            .public_access_block(PublicAccessBlock::all())
+            .object_ownership(ObjectOwnership::BucketOwnerPreferred)
+            .build()
+            .unwrap();
+
+        let fields = bucket.fields();
+        assert_eq!(fields.text("versioning"), Some("enabled"));
+        assert_eq!(fields.text("object_ownership"), Some("BucketOwnerPreferred"));
+
+        let encryption = fields.object("encryption").unwrap();
+        assert_eq!(encryption.text("algorithm"), Some("aws:kms"));
+        assert_eq!(encryption.text("kms_key_id"), Some("alias/logs"));
+        assert_eq!(encryption.boolean("bucket_key"), Some(true));
+
+        let block = fields.object("public_access_block").unwrap();
+        assert_eq!(block.boolean("restrict_public_buckets"), Some(true));
+
+        let plain = BucketBuilder::new("my-logs").build().unwrap().fields();
+        assert_eq!(plain.text("versioning"), Some("disabled"));
+        assert!(plain.object("encryption").is_none());
+
+        let suspended = BucketBuilder::new("my-logs")
+            .versioning(Versioning::Suspended)
+            .build()
+            .unwrap();
+        let changes = suspended.fields().diff(&fields);
+        assert!(changes.iter().any(|c| c.field_name() == "versioning"));
+    }
+
+    #[test]
+    fn encryption_round_trips_through_the_model() {
+        let kms = Encryption::Kms {
+            key_id: None,
+            bucket_key: true,
+        };
+
+        assert_eq!(Encryption::from_model(kms.to_model()), Some(kms));
+        assert_eq!(
+            Encryption::from_model(Encryption::S3.to_model()),
+            Some(Encryption::S3)
+        );
+        assert_eq!(
+            Versioning::from_model(Versioning::Suspended.to_model()),
+            Versioning::Suspended
+        );
+        assert_eq!(Versioning::from_model(None), Versioning::Disabled);
+    }
+
+    #[test]
+    fn public_acls_conflict_with_blocking_them() {
+        let bucket = BucketBuilder::new("my-site")
+            .acl(Acl::PublicRead)
+            .public_access_block(PublicAccessBlock::all())
+            .build();
+        assert!(bucket.is_err());
+
+        let bucket = BucketBuilder::new("my-site")
+            .acl(Acl::PublicRead)
+            .public_access_block(PublicAccessBlock {
+                block_public_policy: true,
+                ..Default::default()
+            })
+            .build();
+        assert!(bucket.is_ok());
+    }
+
+    #[test]
+    fn lifecycle_and_cors_rules_are_part_of_the_fields() {
+        let cors = CorsRule {
+            allowed_origins: vec!["https://example.com".into()],
+            allowed_methods: vec!["GET".into(), "HEAD".into()],
+            max_age_seconds: Some(3600),
+            ..Default::default()
+        };
+        let bucket = BucketBuilder::new("my-assets")
+            .lifecycle_rules(vec![LifecycleRule::new("expire").expire_after(30)])
+            .cors_rules(vec![cors.clone()])
+            .build()
+            .unwrap();
+
+        let fields = bucket.fields();
+        assert_eq!(fields.array("lifecycle_rules")[0].text("id"), Some("expire"));
+        let rule = fields.array("cors_rules")[0];
+        assert_eq!(rule.texts("allowed_methods"), vec!["GET", "HEAD"]);
+        assert_eq!(rule.number("max_age_seconds"), Some(3600));
+        assert_eq!(CorsRule::from(cors.to_model()), cors);
+
+        let without = BucketBuilder::new("my-assets").build().unwrap().fields();
+        let changes = without.diff(&fields);
+        assert!(changes.iter().any(|c| c.field_name() == "cors_rules"));
+        assert!(changes.iter().any(|c| c.field_name() == "lifecycle_rules"));
+
+        let bucket = BucketBuilder::new("my-assets")
+            .cors_rules(vec![CorsRule {
+                allowed_origins: vec!["*".into()],
+                allowed_methods: vec!["PATCH".into()],
+                ..Default::default()
+            }])
+            .build();
+        assert!(bucket.is_err());
+
+        let bucket = BucketBuilder::new("my-assets")
+            .cors_rules(vec![CorsRule {
+                max_age_seconds: Some(0),
+                ..cors
+            }])
+            .build();
+        assert!(bucket.is_err());
+    }
+
+    #[test]
+    fn buckets_drift_when_a_setting_is_changed_behind_our_back() {
+        let bucket = BucketBuilder::new("my-site").build().unwrap();
+        let fields = bucket.fields();
+
+        assert!(!bucket.drifted(&fields));
+        assert!(bucket.drifted(&fields.with_text("versioning", "Enabled")));
+    }
+
+    #[test]
+    fn buckets_are_created_in_their_region() {
+        let bucket = BucketBuilder::new("my-site").build().unwrap();
+        assert!(bucket.create_bucket_configuration().is_none());
+
+        let bucket = BucketBuilder::new("my-site")
+            .region("eu-west-1")
+            .build()
+            .unwrap();
+        let configuration = bucket.create_bucket_configuration().unwrap();
+        assert_eq!(
+            configuration.location_constraint,
+            Some(model::BucketLocationConstraint::EuWest1)
+        );
+
+        let bucket = BucketBuilder::new("my-site")
+            .region("cn-north-1")
+            .build()
+            .unwrap();
+        let configuration = bucket.create_bucket_configuration().unwrap();
+        assert_eq!(
+            configuration.location_constraint,
+            Some(model::BucketLocationConstraint::CnNorth1)
+        );
+    }
+
+    #[test]
+    fn bucket_arns_follow_the_region_partition() {
+        let bucket = BucketBuilder::new("my-site").build().unwrap();
+        assert_eq!(bucket.arn().to_string(), "arn:aws:s3:::my-site");
+
+        let bucket = BucketBuilder::new("my-site")
+            .region("cn-north-1")
+            .build()
+            .unwrap();
+        assert_eq!(bucket.arn().to_string(), "arn:aws-cn:s3:::my-site");
+        assert_eq!(
+            bucket.arn().object("index.html").to_string(),
+            "arn:aws-cn:s3:::my-site/index.html"
+        );
+    }
+
+    #[test]
+    fn parsing_arns_validates_them() {
+        let object: Arn<BucketObject> = "arn:aws-us-gov:s3:::my-site/docs/a:b.txt".parse().unwrap();
+        assert_eq!(object.partition(), Partition::AwsUsGov);
+        assert_eq!(object.bucket_name(), "my-site");
+        assert_eq!(object.key(), "docs/a:b.txt");
+        assert_eq!(object.to_string(), "arn:aws-us-gov:s3:::my-site/docs/a:b.txt");
+
+        let bucket: Arn<Bucket> = "arn:aws:s3:::my-site".parse().unwrap();
+        assert_eq!(bucket.to_string(), Arn::bucket("my-site").to_string());
+
+        assert!(matches!(
+            "s3:::my-site".parse::<Arn<Bucket>>(),
+            Err(ArnError::Malformed(_))
+        ));
+        assert!(matches!(
+            "arn:azure:s3:::my-site".parse::<Arn<Bucket>>(),
+            Err(ArnError::UnknownPartition(_))
+        ));
+        assert!(matches!(
+            "arn:aws:iam::123456789012:role/x".parse::<Arn<Bucket>>(),
+            Err(ArnError::WrongService { .. })
+        ));
+        assert!(matches!(
+            "arn:aws:s3:eu-west-1::my-site".parse::<Arn<Bucket>>(),
+            Err(ArnError::Unexpected { part: "region", .. })
+        ));
+        assert!(matches!(
+            "arn:aws:s3:::My_Site".parse::<Arn<Bucket>>(),
+            Err(ArnError::InvalidResource { .. })
+        ));
+        assert!(matches!(
+            "arn:aws:s3:::my-site".parse::<Arn<BucketObject>>(),
+            Err(ArnError::InvalidResource { .. })
+        ));
+    }
+
+    #[test]
+    fn wildcard_arns_work_as_policy_resources() {
+        let everything = Arn::bucket("my-site").all_objects();
+        assert!(everything.matches("arn:aws:s3:::my-site/a/b.html"));
+        assert!(!everything.matches("arn:aws:s3:::other-site/a/b.html"));
+
+        let pattern: Arn<Bucket> = "arn:aws:s3:::logs-*".parse().unwrap();
+        assert!(pattern.matches("arn:aws:s3:::logs-2021"));
+
+        let resource: crate::iam::Resource = everything.into();
+        assert_eq!(resource.as_str(), "arn:aws:s3:::my-site/*");
+    }
 }
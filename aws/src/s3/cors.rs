@@ -0,0 +1,82 @@
+use aws_sdk_s3::model;
+use luminary::Fields;
+
+/// The methods a CORS rule can allow.
+const CORS_METHODS: &[&str] = &["GET", "PUT", "POST", "DELETE", "HEAD"];
+
+/// Lets browsers on other origins fetch from the bucket,
+/// e.g. web fonts served from an asset bucket.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CorsRule {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub expose_headers: Vec<String>,
+    /// How long browsers may cache the response to a preflight request
+    pub max_age_seconds: Option<i32>,
+}
+
+impl CorsRule {
+    pub(super) fn validate(&self) -> Result<(), String> {
+        if self.allowed_origins.is_empty() || self.allowed_methods.is_empty() {
+            return Err("CORS rules need at least one allowed origin and method".into());
+        }
+
+        if let Some(method) = self
+            .allowed_methods
+            .iter()
+            .find(|method| !CORS_METHODS.contains(&method.as_str()))
+        {
+            return Err(format!(
+                "{} is not a method CORS rules can allow, use one of {}",
+                method,
+                CORS_METHODS.join(", ")
+            ));
+        }
+
+        // S3 reports a missing max age as 0, so an explicit 0 would read back as unset
+        if let Some(seconds) = self.max_age_seconds.filter(|seconds| *seconds <= 0) {
+            return Err(format!(
+                "a CORS max age of {} seconds is not allowed, leave it out to not cache preflight responses",
+                seconds
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn fields(&self) -> Fields {
+        let fields = Fields::empty()
+            .with_texts("allowed_origins", &self.allowed_origins)
+            .with_texts("allowed_methods", &self.allowed_methods)
+            .with_texts("allowed_headers", &self.allowed_headers)
+            .with_texts("expose_headers", &self.expose_headers);
+
+        match self.max_age_seconds {
+            Some(seconds) => fields.with_number("max_age_seconds", seconds),
+            None => fields,
+        }
+    }
+
+    pub(super) fn to_model(&self) -> model::CorsRule {
+        model::CorsRule::builder()
+            .set_allowed_origins(Some(self.allowed_origins.clone()))
+            .set_allowed_methods(Some(self.allowed_methods.clone()))
+            .set_allowed_headers(Some(self.allowed_headers.clone()))
+            .set_expose_headers(Some(self.expose_headers.clone()))
+            .set_max_age_seconds(self.max_age_seconds)
+            .build()
+    }
+}
+
+impl From<model::CorsRule> for CorsRule {
+    fn from(rule: model::CorsRule) -> Self {
+        CorsRule {
+            allowed_origins: rule.allowed_origins.unwrap_or_default(),
+            allowed_methods: rule.allowed_methods.unwrap_or_default(),
+            allowed_headers: rule.allowed_headers.unwrap_or_default(),
+            expose_headers: rule.expose_headers.unwrap_or_default(),
+            max_age_seconds: Some(rule.max_age_seconds).filter(|seconds| *seconds > 0),
+        }
+    }
+}
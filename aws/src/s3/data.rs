@@ -0,0 +1,221 @@
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+use async_trait::async_trait;
+use aws_sdk_s3::SdkError;
+use luminary::{DataSource, Fields};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::info;
+
+use super::{client, website_endpoint, Bucket};
+use crate::{Arn, Aws, AwsApi, Partition};
+
+/// A bucket that exists already, e.g. one owned by another team.
+#[derive(Debug, Clone)]
+pub struct BucketData {
+    name: String,
+}
+
+impl BucketData {
+    pub fn new(name: impl Into<String>) -> Self {
+        BucketData { name: name.into() }
+    }
+}
+
+/// What we find out about an existing bucket.
+#[derive(Debug, Clone)]
+pub struct BucketDetails {
+    pub name: String,
+    pub region: String,
+    pub arn: Arn<Bucket>,
+    /// Only meaningful when the bucket hosts a website
+    pub website_endpoint: String,
+}
+
+/// S3 reports buckets in us-east-1 without a location,
+/// and the oldest ones in eu-west-1 as `EU`.
+fn region_of(location_constraint: Option<&str>) -> String {
+    match location_constraint {
+        None | Some("") => "us-east-1".into(),
+        Some("EU") => "eu-west-1".into(),
+        Some(region) => region.into(),
+    }
+}
+
+impl BucketDetails {
+    fn new(name: &str, region: String) -> Self {
+        BucketDetails {
+            name: name.to_string(),
+            arn: Arn::bucket(name).with_partition(Partition::for_region(&region)),
+            website_endpoint: website_endpoint(name, &region),
+            region,
+        }
+    }
+}
+
+#[async_trait]
+impl DataSource<Aws> for BucketData {
+    type Outputs = BucketDetails;
+
+    fn kind(&self) -> &'static str {
+        "s3_bucket"
+    }
+
+    async fn read(&self, provider: &AwsApi) -> Result<BucketDetails, String> {
+        let client = client(provider);
+
+        info!("looking up bucket {}", self.name);
+        let location = match client.get_bucket_location().bucket(&self.name).send().await {
+            Ok(output) => output.location_constraint,
+            Err(SdkError::ServiceError { err, .. }) if err.code() == Some("NoSuchBucket") => {
+                return Err(format!("bucket {} does not exist", self.name))
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let region = region_of(location.as_ref().map(|location| location.as_str()));
+        Ok(BucketDetails::new(&self.name, region))
+    }
+
+    fn fields(&self, outputs: &BucketDetails) -> Fields {
+        Fields::empty()
+            .with_text("id", &outputs.name)
+            .with_text("region", &outputs.region)
+            .with_text("arn", outputs.arn.to_string())
+            .with_text("website_endpoint", &outputs.website_endpoint)
+    }
+}
+
+/// The outputs another program recorded for one of its modules,
+/// read from the state it keeps in a bucket.
+/// Works like `luminary::RemoteState` otherwise.
+pub struct RemoteState<T> {
+    bucket: String,
+    key: String,
+    module: String,
+    outputs: PhantomData<fn() -> T>,
+}
+
+impl<T> RemoteState<T> {
+    pub fn new(
+        bucket: impl Into<String>,
+        key: impl Into<String>,
+        module: impl Into<String>,
+    ) -> Self {
+        RemoteState {
+            bucket: bucket.into(),
+            key: key.into(),
+            module: module.into(),
+            outputs: PhantomData,
+        }
+    }
+}
+
+impl<T> Debug for RemoteState<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteState")
+            .field("bucket", &self.bucket)
+            .field("key", &self.key)
+            .field("module", &self.module)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<T> DataSource<Aws> for RemoteState<T>
+where
+    T: DeserializeOwned + Serialize + Clone + Debug + Send + Sync + 'static,
+{
+    type Outputs = T;
+
+    fn kind(&self) -> &'static str {
+        "s3_remote_state"
+    }
+
+    async fn read(&self, provider: &AwsApi) -> Result<T, String> {
+        let client = client(provider);
+
+        info!("reading state from {}/{}", self.bucket, self.key);
+        let output = client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let content = output
+            .body
+            .collect()
+            .await
+            .map_err(|e| e.to_string())?
+            .into_bytes();
+
+        let json = std::str::from_utf8(&content)
+            .map_err(|e| format!("state in {}/{} is not text: {}", self.bucket, self.key, e))?;
+        luminary::RealState::from_json(json)?.outputs(&self.module)
+    }
+
+    fn fields(&self, outputs: &T) -> Fields {
+        // Outputs that don't fit into fields, like fractional numbers, are left out
+        let outputs = Fields::serialized(outputs).unwrap_or_else(|_| Fields::empty());
+
+        Fields::empty()
+            .with_text("bucket", &self.bucket)
+            .with_text("key", &self.key)
+            .with_text("module", &self.module)
+            .with_object("outputs", |_| outputs.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions_come_from_the_location_constraint() {
+        assert_eq!(region_of(None), "us-east-1");
+        assert_eq!(region_of(Some("")), "us-east-1");
+        assert_eq!(region_of(Some("EU")), "eu-west-1");
+
+        let details = BucketDetails::new("shared-logs", region_of(Some("cn-north-1")));
+        assert_eq!(details.arn.to_string(), "arn:aws-cn:s3:::shared-logs");
+        assert_eq!(
+            BucketData::new("shared-logs")
+                .fields(&details)
+                .text("region"),
+            Some("cn-north-1")
+        );
+    }
+
+    #[test]
+    fn bucket_arns_can_be_shared_through_the_state() {
+        #[derive(Debug, Clone, Serialize, serde::Deserialize)]
+        struct Storage {
+            logs: Arn<Bucket>,
+        }
+
+        let mut state = luminary::RealState::new();
+        state
+            .set_outputs(
+                "$.module.storage",
+                &Storage {
+                    logs: Arn::bucket("shared-logs"),
+                },
+            )
+            .unwrap();
+        let state = luminary::RealState::from_json(&state.to_json()).unwrap();
+
+        let storage: Storage = state.outputs("$.module.storage").unwrap();
+        assert_eq!(storage.logs.bucket_name(), "shared-logs");
+
+        let mut state = luminary::RealState::new();
+        state
+            .set_outputs(
+                "$",
+                &serde_json::json!({ "logs": "arn:aws:iam::123456789012:role/x" }),
+            )
+            .unwrap();
+        assert!(state.outputs::<Storage>("$").is_err());
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use luminary::Value;
+use walkdir::WalkDir;
+
+use super::{BucketObject, BucketObjectBuilder};
+
+/// Sets the `Cache-Control` header of every file matching `pattern`,
+/// e.g. caching fingerprinted assets forever but never `index.html`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheControl {
+    pub pattern: String,
+    pub value: String,
+}
+
+/// Everything below a local directory, published to a bucket as one object per file,
+/// so a whole `dist/` folder can be defined at once.
+/// Each file ends up under its path relative to the directory, after the `prefix`.
+#[derive(Builder, Clone, Debug)]
+#[builder(
+    setter(strip_option, into),
+    pattern = "owned",
+    build_fn(validate = "Self::validate")
+)]
+pub struct BucketDirectory {
+    bucket: Value<String>,
+    path: PathBuf,
+    #[builder(default)]
+    prefix: String,
+    /// Only files matching one of these are synced, all of them when empty
+    #[builder(default, setter(custom))]
+    include: Vec<String>,
+    /// Files matching one of these are left out, even when included
+    #[builder(default, setter(custom))]
+    exclude: Vec<String>,
+    /// The first matching rule wins
+    #[builder(default, setter(custom))]
+    cache_control: Vec<CacheControl>,
+}
+
+impl BucketDirectoryBuilder {
+    pub fn new() -> Self {
+        BucketDirectoryBuilder::default()
+    }
+
+    pub fn include(mut self, pattern: impl Into<String>) -> Self {
+        self.include
+            .get_or_insert_with(Vec::new)
+            .push(pattern.into());
+        self
+    }
+
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude
+            .get_or_insert_with(Vec::new)
+            .push(pattern.into());
+        self
+    }
+
+    pub fn cache_control(mut self, pattern: impl Into<String>, value: impl Into<String>) -> Self {
+        self.cache_control
+            .get_or_insert_with(Vec::new)
+            .push(CacheControl {
+                pattern: pattern.into(),
+                value: value.into(),
+            });
+        self
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let patterns = self.include.iter().chain(&self.exclude).flatten().chain(
+            self.cache_control
+                .iter()
+                .flatten()
+                .map(|rule| &rule.pattern),
+        );
+
+        for pattern in patterns {
+            glob(pattern)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Like in `.gitignore`, `*` stops at `/` while `**` crosses directories.
+fn glob(pattern: &str) -> Result<Glob, String> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| format!("invalid pattern {}: {}", pattern, e))
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut set = GlobSetBuilder::new();
+    for pattern in patterns {
+        set.add(glob(pattern)?);
+    }
+    set.build().map_err(|e| e.to_string())
+}
+
+/// A file below the directory that should be in the bucket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DirectoryFile {
+    /// Relative to the directory, always separated by `/`
+    relative_key: String,
+    path: PathBuf,
+    cache_control: Option<String>,
+}
+
+impl BucketDirectory {
+    fn key(&self, relative_key: &str) -> String {
+        format!("{}{}", self.prefix, relative_key)
+    }
+
+    /// Walks the directory, sorted by key so plans are stable.
+    fn files(&self) -> Result<Vec<DirectoryFile>, String> {
+        let include = glob_set(&self.include)?;
+        let exclude = glob_set(&self.exclude)?;
+        let cache_control = self
+            .cache_control
+            .iter()
+            .map(|rule| Ok((glob(&rule.pattern)?.compile_matcher(), &rule.value)))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let mut files = Vec::new();
+        for entry in WalkDir::new(&self.path).sort_by_file_name() {
+            let entry =
+                entry.map_err(|e| format!("could not read {}: {}", self.path.display(), e))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let relative_key = relative_key(&self.path, entry.path())?;
+            if (!self.include.is_empty() && !include.is_match(&relative_key))
+                || exclude.is_match(&relative_key)
+            {
+                continue;
+            }
+
+            files.push(DirectoryFile {
+                cache_control: cache_control
+                    .iter()
+                    .find(|(matcher, _)| matcher.is_match(&relative_key))
+                    .map(|(_, value)| value.to_string()),
+                relative_key,
+                path: entry.into_path(),
+            });
+        }
+
+        Ok(files)
+    }
+
+    /// One object for every file, keyed by its path relative to the directory.
+    /// Handed to `Provider::resources_for_each`, each file gets an address of its own,
+    /// e.g. `s3_bucket_object.site["assets/app.js"]`, and shows up on its own in plans.
+    /// Files that are gone are no longer defined, so their objects are deleted.
+    pub fn objects(&self) -> Result<BTreeMap<String, BucketObject>, String> {
+        self.files()?
+            .into_iter()
+            .map(|file| {
+                let object = BucketObjectBuilder::new()
+                    .bucket(self.bucket.clone())
+                    .key(self.key(&file.relative_key))
+                    .file(file.path);
+                let object = match file.cache_control {
+                    Some(cache_control) => object.cache_control(cache_control),
+                    None => object,
+                };
+
+                Ok((
+                    file.relative_key,
+                    object.build().map_err(|e| e.to_string())?,
+                ))
+            })
+            .collect()
+    }
+}
+
+fn relative_key(root: &Path, path: &Path) -> Result<String, String> {
+    let relative = path
+        .strip_prefix(root)
+        .map_err(|_| format!("{} is not below {}", path.display(), root.display()))?;
+
+    let parts: Vec<_> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+
+    Ok(parts.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Aws, AwsApi, AwsDetails};
+    use luminary::{Provider, RealState};
+    use tempfile::TempDir;
+
+    fn site() -> TempDir {
+        let root = TempDir::new().unwrap();
+
+        for file in &[
+            "index.html",
+            "assets/app.3f2a.js",
+            "assets/app.3f2a.js.map",
+            "assets/fonts/inter.woff2",
+            ".DS_Store",
+        ] {
+            let path = root.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, file).unwrap();
+        }
+
+        root
+    }
+
+    #[test]
+    fn files_are_keyed_by_their_relative_path() {
+        let root = site();
+        let directory = BucketDirectoryBuilder::new()
+            .bucket("my-site".to_string())
+            .path(root.path())
+            .exclude("**/*.map")
+            .exclude(".*")
+            .cache_control("assets/**", "public, max-age=31536000, immutable")
+            .cache_control("*", "no-cache")
+            .build()
+            .unwrap();
+
+        let files = directory.files().unwrap();
+        let keys: Vec<_> = files.iter().map(|f| f.relative_key.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "assets/app.3f2a.js",
+                "assets/fonts/inter.woff2",
+                "index.html"
+            ]
+        );
+
+        assert_eq!(files[0].path, root.path().join("assets/app.3f2a.js"));
+        assert_eq!(
+            files[1].cache_control.as_deref(),
+            Some("public, max-age=31536000, immutable")
+        );
+        assert_eq!(files[2].cache_control.as_deref(), Some("no-cache"));
+    }
+
+    #[test]
+    fn includes_narrow_down_the_files() {
+        let root = site();
+        let directory = BucketDirectoryBuilder::new()
+            .bucket("my-site".to_string())
+            .path(root.path())
+            .prefix("v2/")
+            .include("**/*.js")
+            .include("*.html")
+            .build()
+            .unwrap();
+
+        let keys: Vec<_> = directory
+            .files()
+            .unwrap()
+            .into_iter()
+            .map(|file| directory.key(&file.relative_key))
+            .collect();
+        assert_eq!(keys, vec!["v2/assets/app.3f2a.js", "v2/index.html"]);
+    }
+
+    async fn planned(provider: &Provider<Aws>) -> Vec<String> {
+        let plan = provider.plan(&RealState::new()).await.unwrap();
+        plan.steps.into_iter().map(|step| step.address).collect()
+    }
+
+    #[tokio::test]
+    async fn every_file_is_an_object_of_its_own() {
+        let root = site();
+        let directory = BucketDirectoryBuilder::new()
+            .bucket("my-site".to_string())
+            .path(root.path())
+            .exclude("**/*.map")
+            .exclude(".*")
+            .build()
+            .unwrap();
+
+        let api = AwsApi::new(AwsDetails::from_keys("testing", "testing"));
+        let mut provider: Provider<Aws> = Provider::new(api);
+        provider
+            .resources_for_each(
+                "site",
+                directory.objects().unwrap(),
+                |_, _, object| object,
+                [],
+            )
+            .unwrap();
+
+        assert_eq!(
+            planned(&provider).await,
+            vec![
+                r#"$.s3_bucket_object.site["assets/app.3f2a.js"]"#,
+                r#"$.s3_bucket_object.site["assets/fonts/inter.woff2"]"#,
+                r#"$.s3_bucket_object.site["index.html"]"#,
+            ]
+        );
+
+        provider
+            .target(r#"$.s3_bucket_object.site["index.html"]"#)
+            .unwrap();
+        assert_eq!(
+            planned(&provider).await,
+            vec![r#"$.s3_bucket_object.site["index.html"]"#]
+        );
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        let directory = BucketDirectoryBuilder::new()
+            .bucket("my-site".to_string())
+            .path("dist")
+            .exclude("assets/[")
+            .build();
+
+        assert!(directory.is_err());
+    }
+}
@@ -0,0 +1,369 @@
+use aws_sdk_s3::model;
+use luminary::Fields;
+
+use super::StorageClass;
+use crate::Tags;
+
+/// Which objects a lifecycle rule applies to.
+/// An empty filter covers the whole bucket.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LifecycleFilter {
+    pub prefix: Option<String>,
+    pub tags: Tags,
+}
+
+impl LifecycleFilter {
+    fn to_model(&self) -> model::LifecycleRuleFilter {
+        let tags: Vec<model::Tag> = self
+            .tags
+            .iter()
+            .map(|(key, value)| model::Tag::builder().key(key).value(value).build())
+            .collect();
+
+        match (&self.prefix, tags.len()) {
+            (prefix, 0) => model::LifecycleRuleFilter::Prefix(prefix.clone().unwrap_or_default()),
+            (None, 1) => model::LifecycleRuleFilter::Tag(tags[0].clone()),
+            (prefix, _) => model::LifecycleRuleFilter::And(
+                model::LifecycleRuleAndOperator::builder()
+                    .set_prefix(prefix.clone())
+                    .set_tags(Some(tags))
+                    .build(),
+            ),
+        }
+    }
+
+    fn from_model(filter: model::LifecycleRuleFilter) -> Self {
+        let tag = |tag: model::Tag| (tag.key.unwrap_or_default(), tag.value.unwrap_or_default());
+
+        match filter {
+            model::LifecycleRuleFilter::Prefix(prefix) => LifecycleFilter {
+                prefix: Some(prefix).filter(|p| !p.is_empty()),
+                tags: Tags::new(),
+            },
+            model::LifecycleRuleFilter::Tag(single) => LifecycleFilter {
+                prefix: None,
+                tags: std::iter::once(tag(single)).collect(),
+            },
+            model::LifecycleRuleFilter::And(and) => LifecycleFilter {
+                prefix: and.prefix.filter(|p| !p.is_empty()),
+                tags: and.tags.unwrap_or_default().into_iter().map(tag).collect(),
+            },
+            _ => LifecycleFilter::default(),
+        }
+    }
+}
+
+/// Moves objects to a cheaper storage class once they are `days` old.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+    pub days: i32,
+    pub storage_class: StorageClass,
+}
+
+impl Transition {
+    fn fields(&self) -> Fields {
+        Fields::empty()
+            .with_number("days", self.days)
+            .with_text("storage_class", self.storage_class.as_str())
+    }
+}
+
+/// Expires or archives objects as they age, e.g. logs that are only kept for a year.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LifecycleRule {
+    pub id: String,
+    pub enabled: bool,
+    pub filter: LifecycleFilter,
+    pub transitions: Vec<Transition>,
+    pub expiration_days: Option<i32>,
+    /// Counted from the moment a newer version replaced the object,
+    /// so these only matter for versioned buckets.
+    pub noncurrent_transitions: Vec<Transition>,
+    pub noncurrent_expiration_days: Option<i32>,
+}
+
+impl LifecycleRule {
+    pub fn new(id: impl Into<String>) -> Self {
+        LifecycleRule {
+            id: id.into(),
+            enabled: true,
+            filter: LifecycleFilter::default(),
+            transitions: Vec::new(),
+            expiration_days: None,
+            noncurrent_transitions: Vec::new(),
+            noncurrent_expiration_days: None,
+        }
+    }
+
+    pub fn disabled(mut self) -> Self {
+        self.enabled = false;
+        self
+    }
+
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.filter.prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.filter.tags.insert(key, value);
+        self
+    }
+
+    pub fn transition(mut self, days: i32, storage_class: StorageClass) -> Self {
+        self.transitions.push(Transition {
+            days,
+            storage_class,
+        });
+        self
+    }
+
+    pub fn expire_after(mut self, days: i32) -> Self {
+        self.expiration_days = Some(days);
+        self
+    }
+
+    pub fn noncurrent_transition(mut self, days: i32, storage_class: StorageClass) -> Self {
+        self.noncurrent_transitions.push(Transition {
+            days,
+            storage_class,
+        });
+        self
+    }
+
+    pub fn expire_noncurrent_after(mut self, days: i32) -> Self {
+        self.noncurrent_expiration_days = Some(days);
+        self
+    }
+
+    pub(super) fn validate(&self) -> Result<(), String> {
+        if self.id.is_empty() || self.id.len() > 255 {
+            return Err("lifecycle rule ids are between 1 and 255 characters long".into());
+        }
+
+        if self.transitions.is_empty()
+            && self.noncurrent_transitions.is_empty()
+            && self.expiration_days.is_none()
+            && self.noncurrent_expiration_days.is_none()
+        {
+            return Err(format!(
+                "lifecycle rule {} neither transitions nor expires anything",
+                self.id
+            ));
+        }
+
+        let transitions = self.transitions.iter().chain(&self.noncurrent_transitions);
+        for transition in transitions {
+            if transition.storage_class.to_transition_model().is_none() {
+                return Err(format!(
+                    "lifecycle rule {} can't transition objects to {}",
+                    self.id,
+                    transition.storage_class.as_str()
+                ));
+            }
+        }
+
+        let too_soon = self
+            .transitions
+            .iter()
+            .chain(&self.noncurrent_transitions)
+            .map(|transition| transition.days)
+            .chain(self.expiration_days)
+            .chain(self.noncurrent_expiration_days)
+            .any(|days| days < 1);
+        if too_soon {
+            return Err(format!(
+                "lifecycle rule {} has to wait at least a day",
+                self.id
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn fields(&self) -> Fields {
+        let mut fields = Fields::empty()
+            .with_text("id", &self.id)
+            .with_boolean("enabled", self.enabled)
+            .with_array(
+                "transitions",
+                self.transitions.iter().map(Transition::fields),
+            )
+            .with_array(
+                "noncurrent_transitions",
+                self.noncurrent_transitions.iter().map(Transition::fields),
+            );
+        fields = self.filter.tags.record(fields);
+
+        if let Some(prefix) = &self.filter.prefix {
+            fields = fields.with_text("prefix", prefix);
+        }
+        if let Some(days) = self.expiration_days {
+            fields = fields.with_number("expiration_days", days);
+        }
+        if let Some(days) = self.noncurrent_expiration_days {
+            fields = fields.with_number("noncurrent_expiration_days", days);
+        }
+
+        fields
+    }
+
+    pub(super) fn to_model(&self) -> model::LifecycleRule {
+        let status = if self.enabled {
+            model::ExpirationStatus::Enabled
+        } else {
+            model::ExpirationStatus::Disabled
+        };
+
+        let mut rule = model::LifecycleRule::builder()
+            .id(&self.id)
+            .status(status)
+            .filter(self.filter.to_model());
+
+        for transition in &self.transitions {
+            rule = rule.transitions(
+                model::Transition::builder()
+                    .days(transition.days)
+                    .set_storage_class(transition.storage_class.to_transition_model())
+                    .build(),
+            );
+        }
+
+        for transition in &self.noncurrent_transitions {
+            rule = rule.noncurrent_version_transitions(
+                model::NoncurrentVersionTransition::builder()
+                    .noncurrent_days(transition.days)
+                    .set_storage_class(transition.storage_class.to_transition_model())
+                    .build(),
+            );
+        }
+
+        if let Some(days) = self.expiration_days {
+            rule = rule.expiration(model::LifecycleExpiration::builder().days(days).build());
+        }
+
+        if let Some(days) = self.noncurrent_expiration_days {
+            rule = rule.noncurrent_version_expiration(
+                model::NoncurrentVersionExpiration::builder()
+                    .noncurrent_days(days)
+                    .build(),
+            );
+        }
+
+        rule.build()
+    }
+}
+
+impl From<model::LifecycleRule> for LifecycleRule {
+    fn from(rule: model::LifecycleRule) -> Self {
+        let filter = match (rule.filter, rule.prefix) {
+            (Some(filter), _) => LifecycleFilter::from_model(filter),
+            // Rules from before filters existed only had a prefix
+            (None, prefix) => LifecycleFilter {
+                prefix: prefix.filter(|p| !p.is_empty()),
+                tags: Tags::new(),
+            },
+        };
+
+        let transitions = rule
+            .transitions
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|transition| {
+                Some(Transition {
+                    days: transition.days,
+                    storage_class: StorageClass::from_transition_model(transition.storage_class?)?,
+                })
+            })
+            .collect();
+
+        let noncurrent_transitions = rule
+            .noncurrent_version_transitions
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|transition| {
+                Some(Transition {
+                    days: transition.noncurrent_days,
+                    storage_class: StorageClass::from_transition_model(transition.storage_class?)?,
+                })
+            })
+            .collect();
+
+        LifecycleRule {
+            id: rule.id.unwrap_or_default(),
+            enabled: rule.status == Some(model::ExpirationStatus::Enabled),
+            filter,
+            transitions,
+            expiration_days: rule
+                .expiration
+                .map(|expiration| expiration.days)
+                .filter(|days| *days > 0),
+            noncurrent_transitions,
+            noncurrent_expiration_days: rule
+                .noncurrent_version_expiration
+                .map(|expiration| expiration.noncurrent_days)
+                .filter(|days| *days > 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_pick_the_simplest_shape() {
+        let everything = LifecycleFilter::default().to_model();
+        assert_eq!(everything, model::LifecycleRuleFilter::Prefix("".into()));
+
+        let tagged = LifecycleRule::new("r").tag("kind", "log").filter.to_model();
+        assert!(matches!(tagged, model::LifecycleRuleFilter::Tag(_)));
+
+        let both = LifecycleRule::new("r")
+            .prefix("logs/")
+            .tag("kind", "log")
+            .filter;
+        assert!(matches!(
+            both.to_model(),
+            model::LifecycleRuleFilter::And(_)
+        ));
+        assert_eq!(LifecycleFilter::from_model(both.to_model()), both);
+    }
+
+    #[test]
+    fn rules_round_trip_through_the_model() {
+        let rule = LifecycleRule::new("archive-logs")
+            .prefix("logs/")
+            .transition(30, StorageClass::StandardIa)
+            .transition(90, StorageClass::Glacier)
+            .expire_after(365)
+            .noncurrent_transition(7, StorageClass::OnezoneIa)
+            .expire_noncurrent_after(30);
+
+        assert!(rule.validate().is_ok());
+        assert_eq!(LifecycleRule::from(rule.to_model()), rule);
+
+        let fields = rule.fields();
+        assert_eq!(fields.text("prefix"), Some("logs/"));
+        assert_eq!(fields.number("expiration_days"), Some(365));
+        assert_eq!(fields.array("transitions").len(), 2);
+        assert_eq!(
+            fields.array("noncurrent_transitions")[0].text("storage_class"),
+            Some("ONEZONE_IA")
+        );
+    }
+
+    #[test]
+    fn rules_have_to_do_something_sensible() {
+        assert!(LifecycleRule::new("nothing").validate().is_err());
+        assert!(LifecycleRule::new("").expire_after(1).validate().is_err());
+        assert!(LifecycleRule::new("eager")
+            .expire_after(0)
+            .validate()
+            .is_err());
+        assert!(LifecycleRule::new("back")
+            .transition(30, StorageClass::Standard)
+            .validate()
+            .is_err());
+    }
+}
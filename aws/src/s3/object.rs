@@ -0,0 +1,665 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use aws_sdk_s3::client::fluent_builders;
+use aws_sdk_s3::{model, ByteStream, Client, SdkError};
+use luminary::{Creatable, CreateError, Fields, Resource, Template, Value};
+use tracing::info;
+
+use super::{client, Acl, Encryption, StorageClass};
+use crate::{Aws, AwsApi, Tags};
+
+/// The user metadata key we keep the MD5 of the content under.
+/// S3's own ETag is only the MD5 for unencrypted, single part uploads.
+const CONTENT_MD5: &str = "content-md5";
+
+/// Where the content of an object comes from.
+#[derive(Clone, Debug)]
+pub enum Source {
+    Text(String),
+    Bytes(Vec<u8>),
+    /// Rendered whenever the object is uploaded, once the values it embeds are known
+    Template(Template),
+    /// Read from disk whenever the object is uploaded
+    File(PathBuf),
+}
+
+impl Source {
+    fn load(&self) -> Result<Vec<u8>, String> {
+        match self {
+            Source::Text(text) => Ok(text.clone().into_bytes()),
+            Source::Bytes(bytes) => Ok(bytes.clone()),
+            Source::Template(template) => Ok(template.render()?.into_bytes()),
+            Source::File(path) => {
+                std::fs::read(path).map_err(|e| format!("could not read {}: {}", path.display(), e))
+            }
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            Source::File(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+/// Guesses the content type from the key, falling back to the name of the source file
+/// for keys without an extension.
+pub(crate) fn guess_content_type(key: &str, path: Option<&Path>) -> String {
+    let guess = mime_guess::from_path(key)
+        .first()
+        .or_else(|| path.and_then(|path| mime_guess::from_path(path).first()));
+
+    guess
+        .map(|mime| mime.to_string())
+        .unwrap_or_else(|| "application/octet-stream".into())
+}
+
+pub(crate) fn content_md5(content: &[u8]) -> String {
+    format!("{:x}", md5::compute(content))
+}
+
+/// Uploads `content` under `key`, with whatever headers `headers` adds to the request.
+/// Records the MD5 we need to tell whether it has to be uploaded again.
+pub(super) async fn put_object<F>(
+    client: &Client,
+    bucket_name: &str,
+    key: &str,
+    content: Vec<u8>,
+    headers: F,
+) -> Result<Fields, String>
+where
+    F: FnOnce(fluent_builders::PutObject) -> fluent_builders::PutObject,
+{
+    let digest = md5::compute(&content);
+    let md5 = format!("{:x}", digest);
+
+    let request = client
+        .put_object()
+        .bucket(bucket_name)
+        .key(key)
+        // Lets S3 reject the upload if it got corrupted on the way
+        .content_md5(base64::encode(digest.0))
+        .metadata(CONTENT_MD5, &md5)
+        .body(ByteStream::from(content));
+
+    info!("uploading object {}/{}", bucket_name, key);
+    let output = headers(request).send().await.map_err(|e| e.to_string())?;
+
+    let fields = Fields::empty().with_text("content_md5", md5);
+    Ok(match output.e_tag {
+        Some(etag) => fields.with_text("etag", etag.trim_matches('"')),
+        None => fields,
+    })
+}
+
+/// What S3 knows about the object at `key`, if it is still there.
+async fn head_object(
+    client: &Client,
+    bucket_name: &str,
+    key: &str,
+) -> Result<Option<Fields>, String> {
+    info!("reading object {}/{}", bucket_name, key);
+    let output = match client
+        .head_object()
+        .bucket(bucket_name)
+        .key(key)
+        .send()
+        .await
+    {
+        Ok(output) => output,
+        Err(SdkError::ServiceError { err, .. }) if err.code() == Some("NotFound") => {
+            return Ok(None)
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let storage_class = output
+        .storage_class
+        .and_then(StorageClass::from_model)
+        // S3 leaves it out for the standard class
+        .unwrap_or(StorageClass::Standard);
+
+    let mut fields = Fields::empty()
+        .with_text("content_type", output.content_type.unwrap_or_default())
+        .with_text("storage_class", storage_class.as_str());
+
+    if let Some(cache_control) = output.cache_control {
+        fields = fields.with_text("cache_control", cache_control);
+    }
+    if let Some(content_encoding) = output.content_encoding {
+        fields = fields.with_text("content_encoding", content_encoding);
+    }
+    if let Some(etag) = output.e_tag {
+        fields = fields.with_text("etag", etag.trim_matches('"'));
+    }
+
+    let encryption = match output.server_side_encryption {
+        Some(model::ServerSideEncryption::Aes256) => Some(Encryption::S3),
+        Some(model::ServerSideEncryption::AwsKms) => Some(Encryption::Kms {
+            key_id: output.ssekms_key_id,
+            bucket_key: output.bucket_key_enabled,
+        }),
+        _ => None,
+    };
+    if let Some(encryption) = encryption {
+        fields = encryption.record(fields);
+    }
+
+    let mut metadata = output.metadata.unwrap_or_default();
+    // Objects uploaded by something else may not have it
+    if let Some(md5) = metadata.remove(CONTENT_MD5) {
+        fields = fields.with_text("content_md5", md5);
+    }
+
+    Ok(Some(record_metadata(
+        fields,
+        &metadata.into_iter().collect(),
+    )))
+}
+
+async fn delete_object(client: &Client, bucket_name: &str, key: &str) -> Result<(), String> {
+    info!("deleting object {}/{}", bucket_name, key);
+    client
+        .delete_object()
+        .bucket(bucket_name)
+        .key(key)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+fn record_metadata(fields: Fields, metadata: &BTreeMap<String, String>) -> Fields {
+    fields.with_object("metadata", |o| {
+        metadata
+            .iter()
+            .fold(o, |o, (key, value)| o.with_text(key, value))
+    })
+}
+
+/// Changing any of these means uploading the object again.
+const UPLOADED_WITH: &[&str] = &[
+    "content_md5",
+    "content_type",
+    "cache_control",
+    "content_encoding",
+    "storage_class",
+    "metadata",
+    "encryption",
+];
+
+#[derive(Builder, Clone, Debug)]
+#[builder(
+    setter(strip_option, into),
+    pattern = "owned",
+    build_fn(validate = "Self::validate")
+)]
+pub struct BucketObject {
+    bucket: Value<String>,
+    key: String,
+    /// Guessed from the key when not given
+    #[builder(default)]
+    content_type: Option<String>,
+    source: Source,
+    /// Sent back as `x-amz-meta-*` headers. S3 lowercases the keys.
+    #[builder(default, setter(custom))]
+    metadata: BTreeMap<String, String>,
+    #[builder(default)]
+    cache_control: Option<String>,
+    /// E.g. `gzip` for content that was compressed ahead of time
+    #[builder(default)]
+    content_encoding: Option<String>,
+    #[builder(default = "StorageClass::Standard")]
+    storage_class: StorageClass,
+    #[builder(default)]
+    acl: Acl,
+    /// Objects get the default encryption of their bucket when not given
+    #[builder(default)]
+    encryption: Option<Encryption>,
+    #[builder(default)]
+    tags: Tags,
+}
+
+impl BucketObjectBuilder {
+    pub fn new() -> Self {
+        BucketObjectBuilder::default()
+    }
+
+    pub fn content(self, content: impl Into<String>) -> Self {
+        self.source(Source::Text(content.into()))
+    }
+
+    pub fn bytes(self, bytes: impl Into<Vec<u8>>) -> Self {
+        self.source(Source::Bytes(bytes.into()))
+    }
+
+    pub fn file(self, path: impl Into<PathBuf>) -> Self {
+        self.source(Source::File(path.into()))
+    }
+
+    pub fn template(self, template: Template) -> Self {
+        self.source(Source::Template(template))
+    }
+
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata
+            .get_or_insert_with(BTreeMap::new)
+            .insert(key.into().to_lowercase(), value.into());
+        self
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let Some(metadata) = &self.metadata {
+            if metadata.contains_key(CONTENT_MD5) {
+                return Err(format!(
+                    "the {} metadata is used to detect changes to the content",
+                    CONTENT_MD5
+                ));
+            }
+        }
+
+        match &self.source {
+            Some(Source::Template(template)) => template.validate(),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl BucketObject {
+    fn content_type(&self) -> String {
+        self.content_type
+            .clone()
+            .unwrap_or_else(|| guess_content_type(&self.key, self.source.path()))
+    }
+
+    /// What we expect to find once `content` is uploaded, except for the ETag.
+    fn fields(&self, bucket_name: &str, content: &[u8]) -> Fields {
+        let mut fields = Fields::empty()
+            .with_text("bucket", bucket_name)
+            .with_text("key", &self.key)
+            .with_text("content_type", self.content_type())
+            .with_text("content_md5", content_md5(content))
+            .with_text("storage_class", self.storage_class.as_str())
+            .with_text("acl", self.acl.as_str());
+        fields = self.tags.record(fields);
+        fields = record_metadata(fields, &self.metadata);
+
+        if let Some(cache_control) = &self.cache_control {
+            fields = fields.with_text("cache_control", cache_control);
+        }
+        if let Some(content_encoding) = &self.content_encoding {
+            fields = fields.with_text("content_encoding", content_encoding);
+        }
+        if let Some(encryption) = &self.encryption {
+            fields = encryption.record(fields);
+        }
+
+        fields
+    }
+
+    async fn upload(
+        &self,
+        client: &Client,
+        bucket_name: &str,
+        content: Vec<u8>,
+    ) -> Result<Fields, CreateError> {
+        let desired = self.fields(bucket_name, &content);
+
+        let (encryption, key_id, bucket_key) = match &self.encryption {
+            None => (None, None, None),
+            Some(Encryption::S3) => (Some(model::ServerSideEncryption::Aes256), None, None),
+            Some(Encryption::Kms { key_id, bucket_key }) => (
+                Some(model::ServerSideEncryption::AwsKms),
+                key_id.clone(),
+                Some(*bucket_key),
+            ),
+        };
+
+        let uploaded = put_object(client, bucket_name, &self.key, content, |request| {
+            let request = self.metadata.iter().fold(request, |request, (key, value)| {
+                request.metadata(key, value)
+            });
+
+            request
+                .content_type(self.content_type())
+                .set_cache_control(self.cache_control.clone())
+                .set_content_encoding(self.content_encoding.clone())
+                .storage_class(self.storage_class.to_model())
+                .acl(self.acl.to_object_model())
+                .set_server_side_encryption(encryption)
+                .set_ssekms_key_id(key_id)
+                .set_bucket_key_enabled(bucket_key)
+        })
+        .await?;
+
+        if !self.tags.is_empty() {
+            // The object is there already
+            let created = Fields::empty()
+                .with_text("bucket", bucket_name)
+                .with_text("key", &self.key);
+            self.apply_tags(client, bucket_name)
+                .await
+                .map_err(|e| CreateError::partly(created, e))?;
+        }
+
+        Ok(match uploaded.text("etag") {
+            Some(etag) => desired.with_text("etag", etag),
+            None => desired,
+        })
+    }
+
+    async fn apply_acl(&self, client: &Client, bucket_name: &str) -> Result<(), String> {
+        client
+            .put_object_acl()
+            .bucket(bucket_name)
+            .key(&self.key)
+            .acl(self.acl.to_object_model())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    async fn apply_tags(&self, client: &Client, bucket_name: &str) -> Result<(), String> {
+        if self.tags.is_empty() {
+            client
+                .delete_object_tagging()
+                .bucket(bucket_name)
+                .key(&self.key)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            return Ok(());
+        }
+
+        let tagging = self
+            .tags
+            .iter()
+            .fold(model::Tagging::builder(), |tagging, (key, value)| {
+                tagging.tag_set(model::Tag::builder().key(key).value(value).build())
+            })
+            .build();
+
+        client
+            .put_object_tagging()
+            .bucket(bucket_name)
+            .key(&self.key)
+            .tagging(tagging)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Resource<Aws> for BucketObject {
+    async fn read(&self, provider: &AwsApi, current: &Fields) -> Result<Fields, String> {
+        let client = client(provider);
+        let bucket_name = current
+            .text("bucket")
+            .ok_or("no bucket recorded for the object")?;
+        let key = current
+            .text("key")
+            .ok_or("no key recorded for the object")?;
+
+        let mut fields = head_object(&client, bucket_name, key)
+            .await?
+            .ok_or_else(|| format!("object {}/{} no longer exists", bucket_name, key))?;
+
+        // Whatever the bucket encrypts objects with by default is not ours to track,
+        // and neither is the key S3 picked when we didn't give one.
+        fields = match &self.encryption {
+            None => fields.remove("encryption"),
+            Some(Encryption::Kms { key_id: None, .. }) => {
+                let bucket_key = fields
+                    .object("encryption")
+                    .and_then(|encryption| encryption.boolean("bucket_key"))
+                    .unwrap_or_default();
+                Encryption::Kms {
+                    key_id: None,
+                    bucket_key,
+                }
+                .record(fields)
+            }
+            Some(_) => fields,
+        };
+
+        let acl = client
+            .get_object_acl()
+            .bucket(bucket_name)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let acl = Acl::from_grants(&acl.grants.unwrap_or_default());
+
+        let tags: Tags = client
+            .get_object_tagging()
+            .bucket(bucket_name)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .tag_set
+            .unwrap_or_default()
+            .into_iter()
+            .map(|tag| (tag.key.unwrap_or_default(), tag.value.unwrap_or_default()))
+            .collect();
+
+        fields = fields
+            .with_text("bucket", bucket_name)
+            .with_text("key", key)
+            .with_text("acl", acl.as_str());
+
+        Ok(tags.record(fields))
+    }
+
+    async fn update(&self, provider: &AwsApi, current: &Fields) -> Result<Fields, String> {
+        let client = client(provider);
+        let bucket_name = self.bucket.get();
+        let content = self.source.load()?;
+
+        let moved = current.text("bucket") != Some(bucket_name.as_str())
+            || current.text("key") != Some(self.key.as_str());
+
+        if moved {
+            self.delete(provider, current).await?;
+            return Ok(self.upload(&client, &bucket_name, content).await?);
+        }
+
+        let desired = self.fields(&bucket_name, &content);
+        let changes = desired.diff(current);
+        let changed = |name: &str| changes.iter().any(|change| change.field_name() == name);
+
+        if UPLOADED_WITH.iter().any(|name| changed(name)) {
+            // Putting the object again replaces it wholesale
+            return Ok(self.upload(&client, &bucket_name, content).await?);
+        }
+
+        if changed("acl") {
+            info!("updating acl of {}/{}", bucket_name, self.key);
+            self.apply_acl(&client, &bucket_name).await?;
+        }
+
+        if changed("tags") {
+            info!("updating tags of {}/{}", bucket_name, self.key);
+            self.apply_tags(&client, &bucket_name).await?;
+        }
+
+        Ok(match current.text("etag") {
+            Some(etag) => desired.with_text("etag", etag),
+            None => desired,
+        })
+    }
+
+    async fn delete(&self, provider: &AwsApi, current: &Fields) -> Result<(), String> {
+        let client = client(provider);
+        let bucket_name = current
+            .text("bucket")
+            .ok_or("no bucket recorded for the object")?;
+        let key = current
+            .text("key")
+            .ok_or("no key recorded for the object")?;
+
+        delete_object(&client, bucket_name, key).await
+    }
+}
+
+#[async_trait]
+impl Creatable<Aws> for BucketObject {
+    async fn create(&self, provider: &AwsApi) -> Result<Fields, CreateError> {
+        let client = client(provider);
+        let bucket_name = self.bucket.get();
+        let content = self.source.load()?;
+
+        self.upload(&client, &bucket_name, content).await
+    }
+
+    fn kind(&self) -> &'static str {
+        "s3_bucket_object"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_types_are_guessed_from_the_key_or_file() {
+        assert_eq!(guess_content_type("index.html", None), "text/html");
+        assert_eq!(guess_content_type("css/site.css", None), "text/css");
+        assert_eq!(guess_content_type("logo.svg", None), "image/svg+xml");
+        assert_eq!(
+            guess_content_type("about", Some(Path::new("site/about.html"))),
+            "text/html"
+        );
+        assert_eq!(
+            guess_content_type("LICENSE", None),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn sources_load_their_content() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("source.json");
+        std::fs::write(&file, "{\"key\": true}").unwrap();
+
+        let text = Source::Text("{\"key\": true}".into()).load().unwrap();
+        let bytes = Source::Bytes(vec![0, 159, 146, 150]).load().unwrap();
+        let from_file = Source::File(file.clone()).load().unwrap();
+
+        assert_eq!(text, from_file);
+        assert_eq!(bytes, vec![0, 159, 146, 150]);
+        assert_eq!(content_md5(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(content_md5(&text), content_md5(&from_file));
+
+        std::fs::remove_file(&file).unwrap();
+        assert!(Source::File(file).load().is_err());
+    }
+
+    #[test]
+    fn explicit_content_types_win() {
+        let object = BucketObjectBuilder::new()
+            .bucket("my-site".to_string())
+            .key("data")
+            .content_type("application/json")
+            .bytes(b"{}".to_vec())
+            .build()
+            .unwrap();
+        assert_eq!(object.content_type(), "application/json");
+
+        let object = BucketObjectBuilder::new()
+            .bucket("my-site".to_string())
+            .key("data.txt")
+            .content("hello")
+            .build()
+            .unwrap();
+        assert_eq!(object.content_type(), "text/plain");
+    }
+
+    #[test]
+    fn templates_are_checked_when_building() {
+        let config = Template::new(r#"{"assets": "${bucket}"}"#);
+
+        let missing = BucketObjectBuilder::new()
+            .bucket("my-site".to_string())
+            .key("config.json")
+            .template(config.clone())
+            .build();
+        assert!(missing.is_err());
+
+        let object = BucketObjectBuilder::new()
+            .bucket("my-site".to_string())
+            .key("config.json")
+            .template(config.value("bucket", "my-assets".to_string()))
+            .build()
+            .unwrap();
+        assert_eq!(
+            object.source.load().unwrap(),
+            br#"{"assets": "my-assets"}"#.to_vec()
+        );
+    }
+
+    #[test]
+    fn object_settings_are_part_of_the_fields() {
+        let object = BucketObjectBuilder::new()
+            .bucket("my-site".to_string())
+            .key("assets/app.js.gz")
+            .content_type("application/javascript")
+            .content_encoding("gzip")
+            .cache_control("max-age=31536000")
+            .metadata("Build", "1234")
+            .storage_class(StorageClass::StandardIa)
+            .acl(Acl::PublicRead)
+            .encryption(Encryption::S3)
+            .tags(Tags::new().with("team", "web"))
+            .bytes(vec![0x1f, 0x8b])
+            .build()
+            .unwrap();
+
+        let fields = object.fields("my-site", &[0x1f, 0x8b]);
+        assert_eq!(fields.text("content_encoding"), Some("gzip"));
+        assert_eq!(fields.text("cache_control"), Some("max-age=31536000"));
+        assert_eq!(fields.text("storage_class"), Some("STANDARD_IA"));
+        assert_eq!(fields.text("acl"), Some("public-read"));
+        assert_eq!(
+            fields
+                .object("metadata")
+                .and_then(|metadata| metadata.text("build")),
+            Some("1234")
+        );
+        assert_eq!(
+            fields
+                .object("encryption")
+                .and_then(|encryption| encryption.text("algorithm")),
+            Some("AES256")
+        );
+
+        let recompressed = object.fields("my-site", &[0x1f, 0x8b, 0x08]);
+        let changes = recompressed.diff(&fields);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].field_name(), "content_md5");
+        assert!(UPLOADED_WITH.contains(&changes[0].field_name()));
+    }
+
+    #[test]
+    fn the_content_md5_metadata_is_reserved() {
+        let object = BucketObjectBuilder::new()
+            .bucket("my-site".to_string())
+            .key("index.html")
+            .metadata("Content-MD5", "d41d8cd98f00b204e9800998ecf8427e")
+            .content("")
+            .build();
+
+        assert!(object.is_err());
+    }
+}
@@ -1,10 +1,21 @@
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The only version of the state we know how to read.
+const VERSION: usize = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct State {
     version: usize,
     resources: Vec<ResourceState>,
+    /// The inputs every module was last applied with, by its address.
+    #[serde(default)]
+    inputs: BTreeMap<String, serde_json::Value>,
+    /// The outputs of every module by its address, `$` being the program itself.
+    #[serde(default)]
+    outputs: BTreeMap<String, serde_json::Value>,
 }
 
 impl Default for State {
@@ -16,8 +27,10 @@ impl Default for State {
 impl State {
     pub fn new() -> Self {
         Self {
-            version: 1,
+            version: VERSION,
             resources: Vec::new(),
+            inputs: BTreeMap::new(),
+            outputs: BTreeMap::new(),
         }
     }
 
@@ -25,15 +38,137 @@ impl State {
         self.resources.push(resource);
     }
 
+    /// Records `resource` in place of whatever was recorded at its address.
+    pub fn set(&mut self, resource: ResourceState) {
+        match self
+            .resources
+            .iter_mut()
+            .find(|recorded| recorded.address == resource.address)
+        {
+            Some(recorded) => *recorded = resource,
+            None => self.add(resource),
+        }
+    }
+
     pub fn print(&self) {
-        println!("{}", serde_json::to_string_pretty(&self).unwrap());
+        println!("{}", self.to_json());
+    }
+
+    pub fn print_outputs(&self) {
+        println!("{}", serde_json::to_string_pretty(&self.outputs).unwrap());
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<State, String> {
+        let mut state: State =
+            serde_json::from_str(json).map_err(|e| format!("invalid state: {}", e))?;
+
+        if state.version > VERSION {
+            return Err(format!(
+                "the state is at version {}, but only version {} is understood",
+                state.version, VERSION
+            ));
+        }
+
+        // Resources used to be recorded without the leading `$.`
+        for resource in &mut state.resources {
+            if !resource.address.starts_with('$') {
+                resource.address = format!("$.{}", resource.address);
+            }
+        }
+
+        Ok(state)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<State, String> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("could not read state from {}: {}", path.display(), e))?;
+
+        Self::from_json(&json)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_json())
+            .map_err(|e| format!("could not write state to {}: {}", path.display(), e))
+    }
+
+    /// Whether the resource at `address` has to be replaced, see `ResourceState::tainted`.
+    pub fn is_tainted(&self, address: &str) -> bool {
+        self.resources
+            .iter()
+            .any(|resource| resource.address == address && resource.tainted)
+    }
+
+    /// Where every recorded resource is, like `$.s3_bucket.logs`.
+    pub fn addresses(&self) -> impl Iterator<Item = &str> {
+        self.resources
+            .iter()
+            .map(|resource| resource.address.as_str())
+    }
+
+    /// What was recorded for the resource at `address`.
+    pub fn resource(&self, address: &str) -> Option<&Fields> {
+        self.resources
+            .iter()
+            .find(|resource| resource.address == address)
+            .map(|resource| &resource.fields)
+    }
+
+    pub fn set_inputs<T: Serialize>(
+        &mut self,
+        address: impl Into<String>,
+        inputs: &T,
+    ) -> Result<(), String> {
+        let address = address.into();
+        let inputs = serde_json::to_value(inputs)
+            .map_err(|e| format!("could not record the inputs of {}: {}", address, e))?;
+
+        self.inputs.insert(address, inputs);
+        Ok(())
+    }
+
+    /// The inputs the module at `address` was last applied with.
+    pub fn inputs(&self, address: &str) -> Option<&serde_json::Value> {
+        self.inputs.get(address)
+    }
+
+    pub fn set_outputs<T: Serialize>(
+        &mut self,
+        address: impl Into<String>,
+        outputs: &T,
+    ) -> Result<(), String> {
+        let address = address.into();
+        let outputs = serde_json::to_value(outputs)
+            .map_err(|e| format!("could not record the outputs of {}: {}", address, e))?;
+
+        self.outputs.insert(address, outputs);
+        Ok(())
+    }
+
+    /// The outputs recorded for the module at `address`,
+    /// read back as whatever type the module put in.
+    pub fn outputs<T: DeserializeOwned>(&self, address: &str) -> Result<T, String> {
+        let outputs = self
+            .outputs
+            .get(address)
+            .ok_or_else(|| format!("no outputs recorded for {}", address))?;
+
+        T::deserialize(outputs)
+            .map_err(|e| format!("the outputs of {} don't match: {}", address, e))
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResourceState {
     address: String,
     fields: Fields,
+    #[serde(default)]
+    tainted: bool,
 }
 
 impl ResourceState {
@@ -41,6 +176,16 @@ impl ResourceState {
         Self {
             address: address.into(),
             fields,
+            tainted: false,
+        }
+    }
+
+    /// A resource that might only exist in part, because creating it failed
+    /// along the way. It is replaced the next time, whatever its fields say.
+    pub fn tainted(address: impl Into<String>, fields: Fields) -> Self {
+        Self {
+            tainted: true,
+            ..Self::new(address, fields)
         }
     }
 }
@@ -68,11 +213,25 @@ pub struct Difference {
     change: Change,
 }
 
+impl Difference {
+    pub fn field_name(&self) -> &str {
+        &self.field_name
+    }
+}
+
 impl Fields {
     pub fn empty() -> Fields {
         Fields(HashMap::new())
     }
 
+    /// Anything that serializes to an object with text, whole numbers,
+    /// booleans, nested objects and arrays.
+    pub fn serialized<T: Serialize>(value: &T) -> Result<Fields, String> {
+        serde_json::to_value(value)
+            .and_then(serde_json::from_value)
+            .map_err(|e| format!("can't be recorded as fields: {}", e))
+    }
+
     pub fn with_text(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
         self.0.insert(name.into(), Field::Text(value.into()));
         self
@@ -97,6 +256,100 @@ impl Fields {
         self
     }
 
+    pub fn with_array<I>(mut self, name: impl Into<String>, items: I) -> Self
+    where
+        I: IntoIterator<Item = Fields>,
+    {
+        self.0.insert(
+            name.into(),
+            Field::Array(items.into_iter().map(Field::Object).collect()),
+        );
+        self
+    }
+
+    pub fn with_texts<I, S>(mut self, name: impl Into<String>, items: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.0.insert(
+            name.into(),
+            Field::Array(
+                items
+                    .into_iter()
+                    .map(|item| Field::Text(item.into()))
+                    .collect(),
+            ),
+        );
+        self
+    }
+
+    pub fn text(&self, name: impl AsRef<str>) -> Option<&str> {
+        match self.0.get(name.as_ref()) {
+            Some(Field::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn number(&self, name: impl AsRef<str>) -> Option<i32> {
+        match self.0.get(name.as_ref()) {
+            Some(Field::Number(number)) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn boolean(&self, name: impl AsRef<str>) -> Option<bool> {
+        match self.0.get(name.as_ref()) {
+            Some(Field::Boolean(boolean)) => Some(*boolean),
+            _ => None,
+        }
+    }
+
+    pub fn object(&self, name: impl AsRef<str>) -> Option<&Fields> {
+        match self.0.get(name.as_ref()) {
+            Some(Field::Object(fields)) => Some(fields),
+            _ => None,
+        }
+    }
+
+    /// All objects stored in the array at `name`.
+    /// Anything in the array that is not an object is skipped.
+    pub fn array(&self, name: impl AsRef<str>) -> Vec<&Fields> {
+        match self.0.get(name.as_ref()) {
+            Some(Field::Array(items)) => items
+                .iter()
+                .filter_map(|item| match item {
+                    Field::Object(fields) => Some(fields),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// All text stored in the array at `name`.
+    pub fn texts(&self, name: impl AsRef<str>) -> Vec<&str> {
+        match self.0.get(name.as_ref()) {
+            Some(Field::Array(items)) => items
+                .iter()
+                .filter_map(|item| match item {
+                    Field::Text(text) => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The names of all fields, in no particular order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+
     pub fn remove(mut self, name: impl AsRef<str>) -> Self {
         self.0.remove(name.as_ref());
         self
@@ -211,6 +464,43 @@ mod tests {
         assert!(person == other);
     }
 
+    #[test]
+    fn reading_back_fields() {
+        let person = Fields::empty()
+            .with_text("name", "Steve")
+            .with_number("age", 42)
+            .with_boolean("some_bool", true)
+            .with_object("address", |a| a.with_text("city", "London"))
+            .with_array(
+                "pets",
+                vec![
+                    Fields::empty().with_text("name", "Rex"),
+                    Fields::empty().with_text("name", "Tom"),
+                ],
+            );
+
+        assert_eq!(person.text("name"), Some("Steve"));
+        assert_eq!(person.text("age"), None);
+        assert_eq!(person.number("age"), Some(42));
+        assert_eq!(person.boolean("some_bool"), Some(true));
+        assert_eq!(
+            person.object("address").and_then(|a| a.text("city")),
+            Some("London")
+        );
+
+        let pets: Vec<_> = person
+            .array("pets")
+            .into_iter()
+            .filter_map(|pet| pet.text("name"))
+            .collect();
+        assert_eq!(pets, vec!["Rex", "Tom"]);
+        assert!(person.array("name").is_empty());
+
+        let person = person.with_texts("nicknames", vec!["Steve-O", "Stevie"]);
+        assert_eq!(person.texts("nicknames"), vec!["Steve-O", "Stevie"]);
+        assert!(person.texts("pets").is_empty());
+    }
+
     #[test]
     fn differences_between_fields() {
         let mut person = Fields::empty()
@@ -256,4 +546,68 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn outputs_survive_a_round_trip() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Network {
+            bucket_arn: String,
+            subnets: Vec<String>,
+        }
+
+        let network = Network {
+            bucket_arn: "arn:aws:s3:::shared-logs".into(),
+            subnets: vec!["a".into(), "b".into()],
+        };
+
+        let mut state = State::new();
+        state.add(ResourceState::new(
+            "$.s3_bucket.logs",
+            Fields::empty().with_text("id", "shared-logs"),
+        ));
+        state.set_outputs("$.module.network", &network).unwrap();
+
+        let state = State::from_json(&state.to_json()).unwrap();
+        assert_eq!(
+            state.outputs::<Network>("$.module.network").unwrap(),
+            network
+        );
+        assert_eq!(
+            state
+                .resource("$.s3_bucket.logs")
+                .and_then(|f| f.text("id")),
+            Some("shared-logs")
+        );
+        assert!(state.outputs::<Network>("$.module.other").is_err());
+        assert!(state.outputs::<Vec<String>>("$.module.network").is_err());
+
+        let older = State::from_json(r#"{"version": 1, "resources": []}"#).unwrap();
+        assert!(older.outputs::<Network>("$").is_err());
+        assert!(State::from_json(r#"{"version": 2, "resources": []}"#).is_err());
+    }
+
+    #[test]
+    fn tainted_resources_stay_tainted_until_replaced() {
+        let mut state = State::new();
+        state.add(ResourceState::tainted("$.s3_bucket.logs", Fields::empty()));
+        state.add(ResourceState::new("$.s3_bucket.site", Fields::empty()));
+
+        let mut state = State::from_json(&state.to_json()).unwrap();
+        assert!(state.is_tainted("$.s3_bucket.logs"));
+        assert!(!state.is_tainted("$.s3_bucket.site"));
+
+        state.set(ResourceState::new(
+            "$.s3_bucket.logs",
+            Fields::empty().with_text("id", "logs"),
+        ));
+        assert!(!state.is_tainted("$.s3_bucket.logs"));
+        assert_eq!(state.addresses().count(), 2);
+
+        let older = State::from_json(
+            r#"{"version": 1, "resources": [{"address": "s3_bucket.logs", "fields": {}}]}"#,
+        )
+        .unwrap();
+        assert!(older.resource("$.s3_bucket.logs").is_some());
+        assert!(!older.is_tainted("$.s3_bucket.logs"));
+    }
 }
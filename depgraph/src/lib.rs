@@ -1,18 +1,29 @@
 #![allow(dead_code)]
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
 
 use fixedbitset::FixedBitSet;
 use petgraph::graph::NodeIndex;
-use petgraph::visit::Dfs;
+use petgraph::visit::Topo;
+use petgraph::Direction;
 use petgraph::Graph;
 
+mod query;
+
+pub use query::AddressQuery;
+
 #[derive(Clone, Debug)]
 pub struct Address {
     node: NodeIndex,
     human: AddressPath,
 }
 
+impl Address {
+    pub fn path(&self) -> &AddressPath {
+        &self.human
+    }
+}
+
 impl Display for Address {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.human)
@@ -58,63 +69,88 @@ impl Display for AddressPath {
 pub struct Segment {
     pub name: String,
     pub kind: String,
+    /// Tells apart the instances of something defined once per item,
+    /// like `s3_bucket.site["blog"]`
+    pub key: Option<String>,
+}
+
+impl Segment {
+    pub fn new(kind: impl Into<String>, name: impl Into<String>) -> Self {
+        Segment {
+            name: name.into(),
+            kind: kind.into(),
+            key: None,
+        }
+    }
+
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = Some(key.into());
+        self
+    }
 }
 
 impl std::fmt::Display for Segment {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}.{}", self.kind, self.name)
+        write!(f, "{}.{}", self.kind, self.name)?;
+        if let Some(key) = &self.key {
+            write!(
+                f,
+                "[\"{}\"]",
+                key.replace('\\', "\\\\").replace('"', "\\\"")
+            )?;
+        }
+        Ok(())
     }
 }
 
 impl std::fmt::Debug for Segment {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}.{}", self.kind, self.name)
+        write!(f, "{}", self)
     }
 }
 
 #[derive(Debug)]
 pub struct DependencyTracking<T, N> {
     tracked_resources: HashMap<AddressPath, T>,
+    /// Nodes that only group others, like modules, by what is directly within them.
+    /// They are part of the graph but never come up when iterating.
+    groups: HashMap<AddressPath, Vec<Address>>,
     dependency_graph: Graph<AddressPath, N>,
     own_address: Address,
 }
 
 impl<'a, T, N> DependencyTracking<T, N> {
+    /// Every resource after everything it depends on, directly or through the groups
+    /// it is in. A resource within a group comes after whatever the group depends on,
+    /// and whatever depends on a group comes after everything within it.
     pub fn iter(&self) -> DependencyIterator<'_, T, N> {
-        let root_address = AddressPath::Root;
-        let root = self
-            .dependency_graph
-            .node_indices()
-            .find(|i| self.dependency_graph[*i] == root_address)
-            .unwrap();
-
-        let mut dfs = Dfs::new(&self.dependency_graph, root);
+        let mut topo = Topo::new(&self.dependency_graph);
         // Skip the root itself:
-        let _ = dfs.next(&self.dependency_graph);
+        let _ = topo.next(&self.dependency_graph);
 
-        DependencyIterator { deps: self, dfs }
+        DependencyIterator { deps: self, topo }
     }
 }
 
 pub struct DependencyIterator<'a, T, N> {
     deps: &'a DependencyTracking<T, N>,
-    dfs: Dfs<NodeIndex, FixedBitSet>,
+    topo: Topo<NodeIndex, FixedBitSet>,
 }
 
 impl<'a, T, N> Iterator for DependencyIterator<'a, T, N> {
     type Item = (&'a T, &'a AddressPath);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(visited) = self.dfs.next(&self.deps.dependency_graph) {
+        while let Some(visited) = self.topo.next(&self.deps.dependency_graph) {
             let address = self.deps.dependency_graph.node_weight(visited).unwrap();
 
-            self.deps
-                .tracked_resources
-                .get(address)
-                .map(|resource| (resource, address))
-        } else {
-            None
+            // Groups are skipped
+            if let Some(resource) = self.deps.tracked_resources.get(address) {
+                return Some((resource, address));
+            }
         }
+
+        None
     }
 }
 
@@ -138,6 +174,7 @@ impl<T, N> DependencyTracking<T, N> {
 
         DependencyTracking {
             tracked_resources: HashMap::new(),
+            groups: HashMap::new(),
             dependency_graph,
             own_address,
         }
@@ -155,27 +192,125 @@ impl<T, N> DependencyTracking<T, N> {
         value: T,
         edge: N,
     ) -> Address {
-        let segment = Segment {
-            name: name.into(),
-            kind: kind.into(),
-        };
+        self.child_at(Segment::new(kind, name), value, edge)
+    }
+
+    pub fn child_at(&mut self, segment: Segment, value: T, edge: N) -> Address {
+        let address = self.add_node(segment, edge);
+        self.tracked_resources.insert(address.human.clone(), value);
+
+        address
+    }
+
+    /// Adds a node that only groups what is defined while it is the own address,
+    /// like a module. It is never iterated over itself; depending on it means depending
+    /// on everything within it, and whatever it depends on comes before all of that.
+    pub fn group(&mut self, kind: impl Into<String>, name: impl Into<String>, edge: N) -> Address {
+        self.group_at(Segment::new(kind, name), edge)
+    }
+
+    pub fn group_at(&mut self, segment: Segment, edge: N) -> Address {
+        let address = self.add_node(segment, edge);
+        self.groups.insert(address.human.clone(), Vec::new());
+
+        address
+    }
+
+    /// Every resource and group whose address matches `query`.
+    pub fn find(&self, query: &AddressQuery) -> Vec<Address> {
+        self.dependency_graph
+            .node_indices()
+            .filter(|node| {
+                let path = &self.dependency_graph[*node];
+                *path != AddressPath::Root && query.matches(path)
+            })
+            .map(|node| Address {
+                node,
+                human: self.dependency_graph[node].clone(),
+            })
+            .collect()
+    }
+
+    /// The resources at `targets`, or within them for groups,
+    /// along with everything they depend on, however indirectly.
+    pub fn with_dependencies(&self, targets: &[Address]) -> HashSet<AddressPath> {
+        let mut pending: Vec<NodeIndex> = targets
+            .iter()
+            .flat_map(|target| self.members(target))
+            .collect();
+        let mut seen = HashSet::new();
+
+        while let Some(node) = pending.pop() {
+            if !seen.insert(node) {
+                continue;
+            }
+
+            // What something depends on, including what the groups it is in depend on
+            pending.extend(
+                self.dependency_graph
+                    .neighbors_directed(node, Direction::Incoming),
+            );
+        }
+
+        seen.into_iter()
+            .map(|node| &self.dependency_graph[node])
+            .filter(|path| self.tracked_resources.contains_key(path))
+            .cloned()
+            .collect()
+    }
+
+    pub fn is_group(&self, address: &Address) -> bool {
+        self.groups.contains_key(&address.human)
+    }
+
+    pub fn add_dependency(&mut self, from: &Address, to: &Address, edge: N)
+    where
+        N: Clone,
+    {
+        for before in self.members(from) {
+            for after in self.members(to) {
+                self.dependency_graph.add_edge(before, after, edge.clone());
+            }
+        }
 
+        // Groups keep the dependency themselves too
+        if self.is_group(from) || self.is_group(to) {
+            self.dependency_graph.add_edge(from.node, to.node, edge);
+        }
+    }
+
+    fn add_node(&mut self, segment: Segment, edge: N) -> Address {
         let new_address_path = self.own_address.human.extend_with(segment);
-        self.tracked_resources
-            .insert(new_address_path.clone(), value);
         let idx = self.dependency_graph.add_node(new_address_path.clone());
 
         self.dependency_graph
             .add_edge(self.own_address.node, idx, edge);
 
-        Address {
+        let address = Address {
             node: idx,
             human: new_address_path,
+        };
+        if let Some(members) = self.groups.get_mut(&self.own_address.human) {
+            members.push(address.clone());
         }
+
+        address
     }
 
-    pub fn add_dependency(&mut self, from: &Address, to: &Address, edge: N) {
-        self.dependency_graph.add_edge(from.node, to.node, edge);
+    /// Everything that isn't a group within `address`, however deeply nested,
+    /// or just `address` itself if it isn't a group.
+    fn members(&self, address: &Address) -> Vec<NodeIndex> {
+        let mut members = Vec::new();
+        let mut pending = vec![address];
+
+        while let Some(address) = pending.pop() {
+            match self.groups.get(&address.human) {
+                Some(within) => pending.extend(within),
+                None => members.push(address.node),
+            }
+        }
+
+        members
     }
 }
 
@@ -191,3 +326,164 @@ impl AddressPath {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addresses(tracking: &DependencyTracking<&'static str, ()>) -> Vec<String> {
+        let mut addresses: Vec<_> = tracking.iter().map(|(_, path)| path.to_string()).collect();
+        addresses.sort();
+        addresses
+    }
+
+    #[test]
+    fn groups_are_not_iterated_but_depending_on_them_reaches_everything_within() {
+        let mut tracking = DependencyTracking::new();
+        let first = tracking.child("bucket", "first", "first", ());
+
+        let outer = tracking.group("module", "outer", ());
+        let root = tracking.swap_own_address(outer);
+        let inner = tracking.group("module", "inner", ());
+        let outer = tracking.swap_own_address(inner);
+        let nested = tracking.child("bucket", "nested", "nested", ());
+        let inner = tracking.swap_own_address(outer);
+        let sibling = tracking.child("bucket", "sibling", "sibling", ());
+        let outer = tracking.swap_own_address(root);
+
+        let last = tracking.child("bucket", "last", "last", ());
+        tracking.add_dependency(&first, &outer, ());
+        tracking.add_dependency(&outer, &last, ());
+
+        assert!(tracking.is_group(&outer) && tracking.is_group(&inner));
+        assert!(!tracking.is_group(&nested));
+        assert_eq!(
+            addresses(&tracking),
+            vec![
+                "$.bucket.first",
+                "$.bucket.last",
+                "$.module.outer.bucket.sibling",
+                "$.module.outer.module.inner.bucket.nested",
+            ]
+        );
+
+        let graph = &tracking.dependency_graph;
+        for member in [&nested, &sibling] {
+            assert!(graph.contains_edge(first.node, member.node));
+            assert!(graph.contains_edge(member.node, last.node));
+        }
+    }
+
+    fn order(tracking: &DependencyTracking<&'static str, ()>) -> Vec<&'static str> {
+        tracking.iter().map(|(name, _)| *name).collect()
+    }
+
+    fn position(order: &[&str], name: &str) -> usize {
+        order.iter().position(|n| *n == name).unwrap()
+    }
+
+    #[test]
+    fn everything_within_a_group_waits_for_what_the_group_depends_on() {
+        let mut tracking = DependencyTracking::new();
+
+        // Defined before what it ends up depending on
+        let outer = tracking.group("module", "outer", ());
+        let root = tracking.swap_own_address(outer);
+        let inner = tracking.group("module", "inner", ());
+        let outer = tracking.swap_own_address(inner);
+        tracking.child("bucket", "nested", "nested", ());
+        let inner = tracking.swap_own_address(outer);
+        tracking.child("bucket", "sibling", "sibling", ());
+        let outer = tracking.swap_own_address(root);
+
+        let bucket = tracking.child("bucket", "bucket", "bucket", ());
+        let policy = tracking.child("policy", "policy", "policy", ());
+        tracking.add_dependency(&bucket, &outer, ());
+        tracking.add_dependency(&policy, &inner, ());
+
+        let order = order(&tracking);
+        assert_eq!(order.len(), 4);
+        assert!(position(&order, "bucket") < position(&order, "sibling"));
+        assert!(position(&order, "bucket") < position(&order, "nested"));
+        assert!(position(&order, "policy") < position(&order, "nested"));
+    }
+
+    #[test]
+    fn depending_on_a_group_waits_for_everything_within() {
+        let mut tracking = DependencyTracking::new();
+        let last = tracking.child("bucket", "last", "last", ());
+
+        let outer = tracking.group("module", "outer", ());
+        let root = tracking.swap_own_address(outer);
+        let inner = tracking.group("module", "inner", ());
+        let outer = tracking.swap_own_address(inner);
+        tracking.child("bucket", "nested", "nested", ());
+        let _inner = tracking.swap_own_address(outer);
+        let outer = tracking.swap_own_address(root);
+
+        tracking.add_dependency(&outer, &last, ());
+
+        assert_eq!(order(&tracking), vec!["nested", "last"]);
+    }
+
+    #[test]
+    fn keyed_segments_show_their_key() {
+        let mut tracking = DependencyTracking::new();
+        let blog = tracking.child_at(
+            Segment::new("s3_bucket", "site").with_key("blog"),
+            "blog",
+            (),
+        );
+        let quoted = tracking.child_at(
+            Segment::new("s3_bucket", "site").with_key(r#"say "hi""#),
+            "quoted",
+            (),
+        );
+
+        assert_eq!(blog.to_string(), r#"$.s3_bucket.site["blog"]"#);
+        assert_eq!(quoted.to_string(), r#"$.s3_bucket.site["say \"hi\""]"#);
+        assert_eq!(order(&tracking).len(), 2);
+
+        let query = r#"$.s3_bucket.site["say*"]"#.parse().unwrap();
+        let found = tracking.find(&query);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path(), quoted.path());
+    }
+
+    #[test]
+    fn targets_come_with_everything_they_depend_on() {
+        let mut tracking = DependencyTracking::new();
+        let bucket = tracking.child("bucket", "bucket", "bucket", ());
+        let unrelated = tracking.child("bucket", "unrelated", "unrelated", ());
+
+        let module = tracking.group("module", "site", ());
+        let root = tracking.swap_own_address(module);
+        let object = tracking.child("object", "index", "index", ());
+        let policy = tracking.child("policy", "policy", "policy", ());
+        tracking.add_dependency(&object, &policy, ());
+        let module = tracking.swap_own_address(root);
+        tracking.add_dependency(&bucket, &module, ());
+
+        let names = |targets: &[Address]| {
+            let mut names: Vec<_> = tracking
+                .with_dependencies(targets)
+                .iter()
+                .map(|path| path.to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(
+            names(&[policy]),
+            vec![
+                "$.bucket.bucket",
+                "$.module.site.object.index",
+                "$.module.site.policy.policy"
+            ]
+        );
+        assert_eq!(names(std::slice::from_ref(&object)).len(), 2);
+        assert_eq!(names(&[module]).len(), 3);
+        assert_eq!(names(&[unrelated, object]).len(), 3);
+    }
+}
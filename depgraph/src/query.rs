@@ -0,0 +1,275 @@
+use std::str::FromStr;
+
+use crate::{AddressPath, Segment};
+
+/// Text between dots, with the key that may follow it in brackets.
+#[derive(Debug, PartialEq)]
+struct Part {
+    text: String,
+    key: Option<String>,
+}
+
+/// Splits `$.kind.name["key"].kind.name` into its parts.
+/// The leading `$.` is optional.
+fn parts(address: &str) -> Result<Vec<Part>, String> {
+    let rest = match address {
+        "$" | "." | "" => return Ok(Vec::new()),
+        _ => address.strip_prefix("$.").unwrap_or(address),
+    };
+
+    let mut parts = Vec::new();
+    let mut chars = rest.chars().peekable();
+    loop {
+        let mut text = String::new();
+        while let Some(c) = chars.next_if(|c| *c != '.' && *c != '[') {
+            text.push(c);
+        }
+        if text.is_empty() {
+            return Err(format!("{} has an empty segment", address));
+        }
+
+        let key = match chars.next_if_eq(&'[') {
+            Some(_) => Some(key(&mut chars).ok_or_else(|| {
+                format!(
+                    "{} has a key that isn't a quoted string in brackets",
+                    address
+                )
+            })?),
+            None => None,
+        };
+        parts.push(Part { text, key });
+
+        match chars.next() {
+            None => return Ok(parts),
+            Some('.') => continue,
+            Some(c) => return Err(format!("unexpected {:?} in {}", c, address)),
+        }
+    }
+}
+
+/// The rest of `["key"]`, after the opening bracket.
+fn key(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Option<String> {
+    if chars.next()? != '"' {
+        return None;
+    }
+
+    let mut key = String::new();
+    loop {
+        match chars.next()? {
+            '\\' => key.push(chars.next()?),
+            '"' => break,
+            c => key.push(c),
+        }
+    }
+
+    match chars.next()? {
+        ']' => Some(key),
+        _ => None,
+    }
+}
+
+impl FromStr for AddressPath {
+    type Err = String;
+
+    /// Parses what `Display` shows, e.g. `$.module.x.s3_bucket.site["blog"]`.
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        let parts = parts(address)?;
+        if parts.is_empty() {
+            return Ok(AddressPath::Root);
+        }
+        if parts.len() % 2 != 0 {
+            return Err(format!("{} doesn't end in a name", address));
+        }
+
+        parts
+            .chunks(2)
+            .map(|pair| {
+                let (kind, name) = (&pair[0], &pair[1]);
+                if kind.key.is_some() {
+                    return Err(format!("{} has a key after a kind", address));
+                }
+
+                Ok(Segment {
+                    kind: kind.text.clone(),
+                    name: name.text.clone(),
+                    key: name.key.clone(),
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map(AddressPath::Leaf)
+    }
+}
+
+impl AddressPath {
+    pub fn segments(&self) -> &[Segment] {
+        match self {
+            AddressPath::Root => &[],
+            AddressPath::Leaf(segments) => segments,
+        }
+    }
+
+    /// Whether this is `prefix` or within it.
+    pub fn starts_with(&self, prefix: &AddressPath) -> bool {
+        self.segments().starts_with(prefix.segments())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Pattern {
+    /// A `*` in any of these matches anything. Without a key, any key matches.
+    Segment {
+        kind: String,
+        name: String,
+        key: Option<String>,
+    },
+    /// `**`, any number of segments, even none
+    AnySegments,
+}
+
+/// Picks out addresses, e.g. `$.module.three-websites.**` for everything within a module,
+/// `$.s3_bucket.*` for every bucket outside of modules or `$.**.s3_bucket.site` for
+/// every instance of `site`, however deeply nested.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddressQuery(Vec<Pattern>);
+
+impl FromStr for AddressQuery {
+    type Err = String;
+
+    fn from_str(query: &str) -> Result<Self, Self::Err> {
+        let mut parts = parts(query)?.into_iter();
+        let mut patterns = Vec::new();
+
+        while let Some(part) = parts.next() {
+            match part {
+                Part { text, key: None } if text == "**" => patterns.push(Pattern::AnySegments),
+                Part {
+                    text: kind,
+                    key: None,
+                } => match parts.next() {
+                    Some(Part { text: name, key }) => {
+                        patterns.push(Pattern::Segment { kind, name, key })
+                    }
+                    None => return Err(format!("{} doesn't end in a name", query)),
+                },
+                Part { .. } => return Err(format!("{} has a key after a kind", query)),
+            }
+        }
+
+        Ok(AddressQuery(patterns))
+    }
+}
+
+impl AddressQuery {
+    /// Only `address` and everything within it.
+    pub fn within(address: &AddressPath) -> Self {
+        let mut patterns: Vec<_> = address
+            .segments()
+            .iter()
+            .map(|segment| Pattern::Segment {
+                kind: segment.kind.clone(),
+                name: segment.name.clone(),
+                key: segment.key.clone(),
+            })
+            .collect();
+        patterns.push(Pattern::AnySegments);
+
+        AddressQuery(patterns)
+    }
+
+    pub fn matches(&self, address: &AddressPath) -> bool {
+        matches(&self.0, address.segments())
+    }
+}
+
+fn matches(patterns: &[Pattern], segments: &[Segment]) -> bool {
+    match (patterns.split_first(), segments.split_first()) {
+        (None, None) => true,
+        (Some((Pattern::AnySegments, rest)), _) => {
+            matches(rest, segments) || (!segments.is_empty() && matches(patterns, &segments[1..]))
+        }
+        (Some((Pattern::Segment { kind, name, key }, rest)), Some((segment, segments))) => {
+            glob(kind, &segment.kind)
+                && glob(name, &segment.name)
+                && match (key, &segment.key) {
+                    (None, _) => true,
+                    (Some(pattern), Some(key)) => glob(pattern, key),
+                    (Some(_), None) => false,
+                }
+                && matches(rest, segments)
+        }
+        _ => false,
+    }
+}
+
+/// Whether `text` matches `pattern`, where `*` stands for any number of characters.
+fn glob(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            text.starts_with(prefix)
+                && (0..=text.len() - prefix.len())
+                    .filter(|skip| text.is_char_boundary(prefix.len() + skip))
+                    .any(|skip| glob(rest, &text[prefix.len() + skip..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(address: &str) -> AddressPath {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn addresses_parse_back_from_how_they_are_shown() {
+        for address in [
+            "$",
+            "$.s3_bucket.my-bucket",
+            r#"$.module.websites.module.site["blog"].s3_bucket.my-other-bucket"#,
+            r#"$.s3_bucket.site["a.b[\"c\"]"]"#,
+        ] {
+            assert_eq!(path(address).to_string(), address);
+        }
+
+        assert_eq!(path("module.x.s3_bucket.y"), path("$.module.x.s3_bucket.y"));
+        assert_eq!(path("."), AddressPath::Root);
+
+        for broken in [
+            "$.s3_bucket",
+            "$.s3_bucket..y",
+            r#"$.s3_bucket["x"].y"#,
+            "$.s3_bucket.site[blog]",
+            r#"$.s3_bucket.site["blog"#,
+            r#"$.s3_bucket.site["blog"]x"#,
+        ] {
+            assert!(broken.parse::<AddressPath>().is_err(), "{}", broken);
+        }
+    }
+
+    #[test]
+    fn queries_match_by_prefix_and_glob() {
+        let query: AddressQuery = "$.module.three-websites.**".parse().unwrap();
+        assert!(query.matches(&path("$.module.three-websites")));
+        assert!(query.matches(&path(
+            "$.module.three-websites.module.first.s3_bucket.my-other-bucket"
+        )));
+        assert!(!query.matches(&path("$.module.other.s3_bucket.x")));
+
+        let query: AddressQuery = "$.**.s3_bucket.site".parse().unwrap();
+        assert!(query.matches(&path(r#"$.s3_bucket.site["blog"]"#)));
+        assert!(query.matches(&path("$.module.x.s3_bucket.site")));
+        assert!(!query.matches(&path("$.module.x.s3_bucket.site.s3_bucket_object.y")));
+
+        let query: AddressQuery = r#"$.s3_*.site["b*"]"#.parse().unwrap();
+        assert!(query.matches(&path(r#"$.s3_bucket.site["blog"]"#)));
+        assert!(!query.matches(&path(r#"$.s3_bucket.site["shop"]"#)));
+        assert!(!query.matches(&path("$.s3_bucket.site")));
+
+        let within = AddressQuery::within(&path("$.module.x"));
+        assert!(within.matches(&path("$.module.x.s3_bucket.y")));
+        assert!(!within.matches(&path("$.module.xy.s3_bucket.y")));
+        assert!(path("$.module.x.s3_bucket.y").starts_with(&path("$.module.x")));
+    }
+}
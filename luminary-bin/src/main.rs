@@ -1,35 +1,64 @@
-use aws::{s3, Arn, Aws, AwsApi, AwsDetails};
-use luminary::Provider;
+use aws::iam::{self, PolicyDocument, PolicyStatementBuilder};
+use aws::{s3, Arn, Aws, AwsApi, AwsDetails, Tags};
+use luminary::{Provider, RealState};
+use std::collections::BTreeMap;
+use std::path::Path;
 
-use luminary::ModuleDefinition;
+use luminary::{ModuleDefinition, Template, Value};
+use serde::Serialize;
 
 use tracing_log::LogTracer;
 use tracing_subscriber::{EnvFilter, Registry, fmt};
 use tracing_subscriber::layer::SubscriberExt;
 
+const STATE: &str = "luminary.state.json";
+
 #[derive(Debug)]
-struct MyWebsite {
-    bucket_name: &'static str,
+struct MyWebsite;
+
+#[derive(Clone, Serialize)]
+struct MyWebsiteInputs {
+    pub bucket_name: String,
+    /// Where the content comes from, shown in `f.json`
+    pub source: Value<String>,
+}
+
+impl MyWebsiteInputs {
+    fn new(bucket_name: impl Into<String>, source: impl Into<Value<String>>) -> Self {
+        MyWebsiteInputs {
+            bucket_name: bucket_name.into(),
+            source: source.into(),
+        }
+    }
 }
 
-// Will be used for something meaningful down the line
-#[allow(dead_code)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 struct MyWebsiteOutput {
     pub arn: Arn<s3::Bucket>,
 }
 
 impl ModuleDefinition<Aws> for MyWebsite {
-    type Inputs = &'static str;
+    type Inputs = MyWebsiteInputs;
     type Outputs = MyWebsiteOutput;
 
-    fn define(&self, provider: &mut Provider<Aws>) -> MyWebsiteOutput {
+    fn validate(&self, inputs: &MyWebsiteInputs) -> Result<(), String> {
+        if inputs.bucket_name.is_empty() {
+            return Err("bucket_name can't be empty".into());
+        }
+
+        Ok(())
+    }
+
+    fn define(&self, inputs: &MyWebsiteInputs, provider: &mut Provider<Aws>) -> MyWebsiteOutput {
         let bucket = provider.resource(
             "my-other-bucket",
             |api| {
-                api.s3_bucket(self.bucket_name)
+                api.s3_bucket(&inputs.bucket_name)
+                    .acl(s3::Acl::PublicRead)
+                    .tags(Tags::new().with("kind", "demo"))
                     .website(s3::Website {
                         index_document: "index.html".into(),
+                        ..Default::default()
                     })
                     .build()
                     .unwrap()
@@ -46,62 +75,64 @@ impl ModuleDefinition<Aws> for MyWebsite {
                     .bucket(bucket.name())
                     .key("f.json")
                     .content_type("application/json")
-                    .content("{\"key\": true}")
+                    .template(
+                        Template::new(
+                            r#"{"key": true, "bucket": "${bucket}", "source": "${source}"}"#,
+                        )
+                        .value("bucket", bucket.arn())
+                        .value("source", inputs.source.clone()),
+                    )
                     .build()
                     .unwrap()
             },
             [],
         );
 
+        let public_can_read = PolicyStatementBuilder::default()
+            .sid("PublicReadGetObject".to_string())
+            .allow()
+            .principal(iam::Principal::AWS("*".into()))
+            .action(iam::Action::new("s3:GetObject"))
+            .resource(bucket.arn())
+            .resource(bucket.arn().all_objects())
+            .build()
+            .unwrap();
+
+        let _policy = provider.resource(
+            "can-be-read",
+            |api| {
+                api.s3_bucket_policy()
+                    .bucket_of(&bucket)
+                    .policy(PolicyDocument::new(vec![public_can_read]))
+                    .build()
+                    .unwrap()
+            },
+            [&bucket],
+        );
+
         MyWebsiteOutput { arn: bucket.arn() }
     }
 }
 
 #[derive(Debug)]
-struct ThreeWebsites {
-    sites: (
-        <MyWebsite as ModuleDefinition<Aws>>::Inputs,
-        <MyWebsite as ModuleDefinition<Aws>>::Inputs,
-        <MyWebsite as ModuleDefinition<Aws>>::Inputs,
-    ),
-}
+struct Websites;
 
-impl ModuleDefinition<Aws> for ThreeWebsites {
-    type Inputs = (
-        <MyWebsite as ModuleDefinition<Aws>>::Inputs,
-        <MyWebsite as ModuleDefinition<Aws>>::Inputs,
-        <MyWebsite as ModuleDefinition<Aws>>::Inputs,
-    );
-    type Outputs = (
-        <MyWebsite as ModuleDefinition<Aws>>::Outputs,
-        <MyWebsite as ModuleDefinition<Aws>>::Outputs,
-        <MyWebsite as ModuleDefinition<Aws>>::Outputs,
-    );
+impl ModuleDefinition<Aws> for Websites {
+    type Inputs = BTreeMap<String, <MyWebsite as ModuleDefinition<Aws>>::Inputs>;
+    type Outputs = BTreeMap<String, <MyWebsite as ModuleDefinition<Aws>>::Outputs>;
 
-    fn define(&self, providers: &mut Provider<Aws>) -> Self::Outputs {
-        let first = providers.module(
-            "first",
-            MyWebsite {
-                bucket_name: self.sites.0,
-            },
-            [],
-        );
-        let second = providers.module(
-            "second",
-            MyWebsite {
-                bucket_name: self.sites.1,
-            },
-            [],
-        );
-        let third = providers.module(
-            "third",
-            MyWebsite {
-                bucket_name: self.sites.2,
-            },
-            [],
-        );
+    fn validate(&self, sites: &Self::Inputs) -> Result<(), String> {
+        sites.values().try_for_each(|site| MyWebsite.validate(site))
+    }
 
-        (first.outputs(), second.outputs(), third.outputs())
+    fn define(&self, sites: &Self::Inputs, providers: &mut Provider<Aws>) -> Self::Outputs {
+        // Already validated along with the inputs of this module
+        providers
+            .modules_for_each("site", MyWebsite, sites.clone(), [])
+            .unwrap()
+            .into_iter()
+            .map(|(key, site)| (key, site.outputs()))
+            .collect()
     }
 }
 
@@ -115,7 +146,7 @@ pub async fn main() -> Result<(), String> {
 
     tracing::subscriber::set_global_default(subscriber).unwrap();
 
-    let details = AwsDetails::from_env().map_err(|e| format!("Missing env key: {}", e))?;
+    let details = AwsDetails::from_env()?;
 
     let api = AwsApi::new(details);
 
@@ -127,25 +158,49 @@ pub async fn main() -> Result<(), String> {
         [],
     );
 
-    let x = provider.module(
+    let website = provider.module(
         "my-fancy-module",
-        MyWebsite {
-            bucket_name: "luminary-rs-module-1",
-        },
+        MyWebsite,
+        MyWebsiteInputs::new("luminary-rs-module-1", b.arn()),
         [&b],
-    );
-
-    let _three_sites = provider.module(
-        "three-websites",
-        ThreeWebsites {
-            sites: ("luminary-rs-1", "luminary-rs-2", "luminary-rs-3"),
-        },
+    )?;
+
+    let _sites = provider.module(
+        "websites",
+        Websites,
+        vec![
+            ("first", "luminary-rs-1", "the first one"),
+            ("second", "luminary-rs-2", "the second one"),
+            ("third", "luminary-rs-3", "the third one"),
+        ]
+        .into_iter()
+        .map(|(key, bucket_name, source)| {
+            (key.to_string(), MyWebsiteInputs::new(bucket_name, source))
+        })
+        .collect(),
         [&b],
-    );
-
-    let state = provider.create().await?;
-
-    // state.print();
+    )?;
+
+    provider.outputs(website.outputs());
+
+    let known = if Path::new(STATE).exists() {
+        RealState::load(STATE)?
+    } else {
+        RealState::new()
+    };
+    print!("{}", provider.plan(&known).await?);
+
+    let state = match provider.apply(&known).await {
+        Ok(state) => state,
+        Err(failed) => {
+            // Keeps track of what has to be replaced next time
+            failed.state.save(STATE)?;
+            return Err(failed.into());
+        }
+    };
+    state.save(STATE)?;
+
+    state.print_outputs();
 
     Ok(())
 }
@@ -0,0 +1,152 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+
+use crate::{Cloud, Creatable, CreateError, Fields, Produce, Resource, Value};
+
+/// Something that already exists in the cloud and is only read,
+/// never created or changed, like a bucket managed by another team.
+#[async_trait]
+pub trait DataSource<C: Cloud>: std::fmt::Debug + Send + Sync {
+    type Outputs: Clone + std::fmt::Debug + Send + Sync + 'static;
+
+    fn kind(&self) -> &'static str;
+
+    async fn read(&self, provider: &<C as Cloud>::ProviderApi) -> Result<Self::Outputs, String>;
+
+    /// How the outputs are recorded in the state.
+    fn fields(&self, outputs: &Self::Outputs) -> Fields;
+}
+
+/// A data source as it sits in the dependency graph.
+/// Its outputs are only known once it has been read.
+#[derive(Debug)]
+pub struct Data<D, O> {
+    source: D,
+    outputs: Arc<Mutex<Option<O>>>,
+}
+
+impl<D, O> Data<D, O>
+where
+    O: Clone + Send + Sync + 'static,
+{
+    pub(crate) fn new(source: D) -> Self {
+        Data {
+            source,
+            outputs: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// What was read, if it was read already.
+    pub fn outputs(&self) -> Option<O> {
+        self.outputs.lock().unwrap().clone()
+    }
+
+    /// Picks a single output, to be passed on to the resources that need it.
+    /// Resolving it before the data source was read is a bug in the dependencies.
+    pub fn output<T, F>(&self, pick: F) -> Value<T>
+    where
+        T: Clone + 'static,
+        F: Fn(&O) -> T + Clone + Send + Sync + 'static,
+    {
+        Value::Reference(Box::new(Output {
+            outputs: Arc::clone(&self.outputs),
+            pick,
+        }))
+    }
+
+    async fn refresh<C>(&self, provider: &<C as Cloud>::ProviderApi) -> Result<Fields, String>
+    where
+        C: Cloud,
+        D: DataSource<C, Outputs = O>,
+    {
+        let outputs = self.source.read(provider).await?;
+        let fields = self.source.fields(&outputs);
+        *self.outputs.lock().unwrap() = Some(outputs);
+
+        Ok(fields)
+    }
+}
+
+/// One of the outputs of a data source, once it was read.
+struct Output<O, F> {
+    outputs: Arc<Mutex<Option<O>>>,
+    pick: F,
+}
+
+impl<O, F: Clone> Clone for Output<O, F> {
+    fn clone(&self) -> Self {
+        Output {
+            outputs: Arc::clone(&self.outputs),
+            pick: self.pick.clone(),
+        }
+    }
+}
+
+impl<O, F, T> Produce<T> for Output<O, F>
+where
+    F: Fn(&O) -> T + Clone,
+{
+    fn get(&self) -> T {
+        self.try_get()
+            .expect("data source has to be read before its outputs are used")
+    }
+
+    fn try_get(&self) -> Option<T> {
+        self.outputs.lock().unwrap().as_ref().map(&self.pick)
+    }
+}
+
+#[async_trait]
+impl<C, D, O> Creatable<C> for Data<D, O>
+where
+    C: Cloud,
+    D: DataSource<C, Outputs = O>,
+    O: Clone + std::fmt::Debug + Send + Sync + 'static,
+{
+    fn kind(&self) -> &'static str {
+        self.source.kind()
+    }
+
+    async fn create(&self, provider: &<C as Cloud>::ProviderApi) -> Result<Fields, CreateError> {
+        Ok(self.refresh::<C>(provider).await?)
+    }
+}
+
+/// Reading again is all a data source ever does.
+#[async_trait]
+impl<C, D, O> Resource<C> for Data<D, O>
+where
+    C: Cloud,
+    D: DataSource<C, Outputs = O>,
+    O: Clone + std::fmt::Debug + Send + Sync + 'static,
+{
+    async fn read(
+        &self,
+        provider: &<C as Cloud>::ProviderApi,
+        _current: &Fields,
+    ) -> Result<Fields, String> {
+        self.refresh::<C>(provider).await
+    }
+
+    async fn update(
+        &self,
+        provider: &<C as Cloud>::ProviderApi,
+        _current: &Fields,
+    ) -> Result<Fields, String> {
+        self.refresh::<C>(provider).await
+    }
+
+    async fn delete(
+        &self,
+        _provider: &<C as Cloud>::ProviderApi,
+        _current: &Fields,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Once read, there is nothing left to do
+    fn drifted(&self, _current: &Fields) -> bool {
+        false
+    }
+}
@@ -1,14 +1,27 @@
 use async_trait::async_trait;
+use std::collections::BTreeMap;
+
 use dyn_clone::DynClone;
+use serde::Serialize;
 
+mod data;
+mod plan;
 mod provider;
+mod remote;
+mod template;
 mod value;
 
 // Re-export
 pub use clutter::Fields;
+pub use data::{Data, DataSource};
+pub use plan::{Action, InputChange, Plan, Step};
+pub use provider::ApplyError;
 pub use provider::DependencyKind;
+pub use provider::Meta;
 pub use provider::Provider;
-pub use value::Value;
+pub use remote::RemoteState;
+pub use template::Template;
+pub use value::{Value, KNOWN_AFTER_APPLY};
 
 pub struct Module<MD, C>
 where
@@ -34,20 +47,98 @@ pub trait ModuleDefinition<C: Cloud>: std::fmt::Debug
 where
     Self: Sized,
 {
-    type Inputs;
-    type Outputs;
+    /// Handed to `define`, and recorded so changes to them show up in plans.
+    /// Late-bound `Value`s are recorded once they are known.
+    type Inputs: Serialize + Send + Sync + 'static;
+    /// Recorded in the state once everything was created.
+    type Outputs: Serialize + Send + Sync + 'static;
+
+    /// Checks the inputs before anything in the module is defined.
+    fn validate(&self, _inputs: &Self::Inputs) -> Result<(), String> {
+        Ok(())
+    }
 
-    // TODO is this right?
-    fn define(&self, providers: &mut Provider<C>) -> Self::Outputs;
+    fn define(&self, inputs: &Self::Inputs, providers: &mut Provider<C>) -> Self::Outputs;
 }
 
+/// The remaining "RUD" of CRUD.
+/// Each of these get the `Fields` that were recorded for the resource
+/// the last time we interacted with it, be it on `create` or on a `read`.
 #[async_trait]
-pub trait Resource<C: Cloud>: Creatable<C> + std::fmt::Debug + Send + Sync {}
+pub trait Resource<C: Cloud>: Creatable<C> + std::fmt::Debug + Send + Sync {
+    /// Refreshes what we know about the resource from the cloud.
+    async fn read(
+        &self,
+        provider: &<C as Cloud>::ProviderApi,
+        current: &Fields,
+    ) -> Result<Fields, String>;
+
+    /// Changes the resource in place so that it matches its definition.
+    async fn update(
+        &self,
+        provider: &<C as Cloud>::ProviderApi,
+        current: &Fields,
+    ) -> Result<Fields, String>;
+
+    async fn delete(
+        &self,
+        provider: &<C as Cloud>::ProviderApi,
+        current: &Fields,
+    ) -> Result<(), String>;
+
+    /// Whether `current`, as `read` found it, differs from the definition.
+    /// Resources that can only tell while updating are always updated.
+    fn drifted(&self, _current: &Fields) -> bool {
+        true
+    }
+}
 
 #[async_trait]
 pub trait Creatable<C: Cloud>: std::fmt::Debug + Send + Sync {
     fn kind(&self) -> &'static str;
-    async fn create(&self, provider: &<C as Cloud>::ProviderApi) -> Result<Fields, String>;
+    async fn create(&self, provider: &<C as Cloud>::ProviderApi) -> Result<Fields, CreateError>;
+}
+
+/// Creating stopped part way. Whatever exists already is described by `fields`,
+/// so it can be deleted before creating it again. Empty if nothing was created.
+#[derive(Debug)]
+pub struct CreateError {
+    pub fields: Fields,
+    pub message: String,
+}
+
+impl CreateError {
+    /// Failed after what `fields` describes was created.
+    pub fn partly(fields: Fields, message: impl Into<String>) -> Self {
+        CreateError {
+            fields,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for CreateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<String> for CreateError {
+    fn from(message: String) -> Self {
+        CreateError::partly(Fields::empty(), message)
+    }
+}
+
+impl From<&str> for CreateError {
+    fn from(message: &str) -> Self {
+        CreateError::partly(Fields::empty(), message)
+    }
+}
+
+impl From<CreateError> for String {
+    fn from(error: CreateError) -> Self {
+        error.message
+    }
 }
 
 /// A very intersting trait that configures
@@ -74,18 +165,51 @@ pub struct KnownState {}
 /// It will contain references to providers, resources, and attributes.
 /// If an `apply` operation is successful the `DesiredState` should become the `KnownState`
 /// and match up with `RealState`
-pub struct DesiredState {}
+#[derive(Debug, Default)]
+pub struct DesiredState {
+    /// The inputs of every module by its address,
+    /// with values that are only known after apply left as placeholders.
+    inputs: BTreeMap<String, serde_json::Value>,
+}
 
 impl DesiredState {
+    pub fn new() -> Self {
+        DesiredState::default()
+    }
+
     /// Combines both states to be tracked together
-    pub fn merge(self, _other: DesiredState) -> Self {
-        // TODO
+    pub fn merge(mut self, other: DesiredState) -> Self {
+        self.inputs.extend(other.inputs);
         self
     }
+
+    pub(crate) fn set_inputs<T: Serialize>(
+        &mut self,
+        address: impl Into<String>,
+        inputs: &T,
+    ) -> Result<(), String> {
+        let address = address.into();
+        let inputs = serde_json::to_value(inputs)
+            .map_err(|e| format!("could not record the inputs of {}: {}", address, e))?;
+
+        self.inputs.insert(address, inputs);
+        Ok(())
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item = (&str, &serde_json::Value)> {
+        self.inputs
+            .iter()
+            .map(|(address, inputs)| (address.as_str(), inputs))
+    }
 }
 
 pub trait Produce<T>: DynClone {
     fn get(&self) -> T;
+
+    /// Like `get`, but `None` while the value is only known after apply.
+    fn try_get(&self) -> Option<T> {
+        Some(self.get())
+    }
 }
 
 // Here be dragons...
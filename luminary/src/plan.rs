@@ -0,0 +1,65 @@
+use std::fmt;
+
+/// What applying would do to a single resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Not in the state yet
+    Create,
+    /// In the state, so it is read and brought in line with its definition
+    Update,
+    /// Deleted and created again, because it was tainted or asked to be replaced
+    Replace,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub address: String,
+    pub action: Action,
+}
+
+/// Module inputs that differ from the ones recorded in the state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputChange {
+    pub module: String,
+    /// `None` when the module was never applied
+    pub before: Option<serde_json::Value>,
+    pub after: serde_json::Value,
+}
+
+/// What applying the desired state on top of a known state would do.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Plan {
+    pub steps: Vec<Step>,
+    pub input_changes: Vec<InputChange>,
+    /// Only what was targeted and what it depends on is covered
+    pub partial: bool,
+    pub warnings: Vec<String>,
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for warning in &self.warnings {
+            writeln!(f, "warning: {}", warning)?;
+        }
+
+        for step in &self.steps {
+            let symbol = match step.action {
+                Action::Create => "+",
+                Action::Update => "~",
+                Action::Replace => "-/+",
+            };
+            writeln!(f, "{} {}", symbol, step.address)?;
+        }
+
+        for change in &self.input_changes {
+            writeln!(f, "inputs of {} changed:", change.module)?;
+            match &change.before {
+                Some(before) => writeln!(f, "  - {}", before)?,
+                None => writeln!(f, "  - (none)")?,
+            }
+            writeln!(f, "  + {}", change.after)?;
+        }
+
+        Ok(())
+    }
+}
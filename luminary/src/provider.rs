@@ -1,19 +1,102 @@
+use std::collections::{BTreeMap, HashSet};
 use std::sync::Arc;
 
-use async_trait::async_trait;
 use clutter::ResourceState;
-use depgraph::{Address, DependencyTracking};
+use depgraph::{Address, AddressPath, AddressQuery, DependencyTracking, Segment};
+use serde::Serialize;
 use tracing::{Level, event, instrument};
 
-use crate::{Cloud, Creatable, Module, ModuleDefinition, RealState, Resource};
+use crate::{
+    Action, Cloud, Data, DataSource, DesiredState, InputChange, Module, ModuleDefinition, Plan,
+    RealState, Resource, Step,
+};
 
 #[derive(Debug)]
 pub struct Provider<C: Cloud> {
     api: C::ProviderApi,
-    dependencies: DependencyTracking<Arc<dyn Creatable<C>>, DependencyKind>,
+    dependencies: DependencyTracking<Arc<dyn Resource<C>>, DependencyKind>,
+    desired: DesiredState,
+    inputs: Vec<Pending>,
+    outputs: Vec<Pending>,
+    targets: Vec<(String, AddressQuery)>,
+    replacements: Vec<(String, AddressQuery)>,
 }
 
-#[derive(Debug)]
+/// What `plan` and `apply` are limited to, if anything was targeted,
+/// and what they replace.
+struct Selection {
+    addresses: Option<HashSet<AddressPath>>,
+    replaced: HashSet<AddressPath>,
+    warnings: Vec<String>,
+}
+
+impl Selection {
+    /// `None` if what is `known` about the resource matches its definition.
+    fn action<C: Cloud>(
+        &self,
+        resource: &dyn Resource<C>,
+        address: &AddressPath,
+        known: &RealState,
+    ) -> Option<Action> {
+        let recorded = address.to_string();
+
+        match known.resource(&recorded) {
+            None => Some(Action::Create),
+            Some(_) if known.is_tainted(&recorded) || self.replaced.contains(address) => {
+                Some(Action::Replace)
+            }
+            Some(current) if resource.drifted(current) => Some(Action::Update),
+            Some(_) => None,
+        }
+    }
+
+    fn includes(&self, address: &AddressPath) -> bool {
+        match &self.addresses {
+            Some(addresses) => addresses.contains(address),
+            None => true,
+        }
+    }
+
+    /// Whether anything within the module at `address` is included
+    fn touches(&self, address: &str) -> bool {
+        match (&self.addresses, address.parse::<AddressPath>()) {
+            (None, _) => true,
+            (Some(addresses), Ok(module)) => addresses.iter().any(|a| a.starts_with(&module)),
+            (Some(_), Err(_)) => false,
+        }
+    }
+}
+
+/// Module inputs and outputs can hold values that are only known once resources exist,
+/// so they are serialized after everything was created.
+struct Pending {
+    address: String,
+    serialize: Box<dyn Fn() -> Result<serde_json::Value, String> + Send + Sync>,
+}
+
+impl Pending {
+    fn new<T>(address: impl Into<String>, value: Arc<T>) -> Self
+    where
+        T: Serialize + Send + Sync + 'static,
+    {
+        Pending {
+            address: address.into(),
+            serialize: Box::new(move || {
+                serde_json::to_value(value.as_ref()).map_err(|e| e.to_string())
+            }),
+        }
+    }
+}
+
+impl std::fmt::Debug for Pending {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pending")
+            .field("address", &self.address)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum DependencyKind {
     Resource,
     Module,
@@ -56,23 +139,12 @@ impl<R> Clone for Meta<R> {
     }
 }
 
-/*
- * TODO: need to move this to a better place
- */
-#[derive(Debug)]
-struct FelipeFakeModule;
-
-#[async_trait]
-impl<C: Cloud> Creatable<C> for FelipeFakeModule {
-    fn kind(&self) -> &'static str {
-        "felipe_fake_module"
-    }
+/// Only the outputs of a module end up in the state.
+struct ModuleOutputs<MD: ModuleDefinition<C>, C: Cloud>(Arc<Module<MD, C>>);
 
-    async fn create(
-        &self,
-        _provider: &<C as Cloud>::ProviderApi,
-    ) -> Result<clutter::Fields, String> {
-        Ok(clutter::Fields::empty())
+impl<MD: ModuleDefinition<C>, C: Cloud> Serialize for ModuleOutputs<MD, C> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.outputs.serialize(serializer)
     }
 }
 
@@ -81,9 +153,102 @@ impl<C: Cloud> Provider<C> {
         Self {
             api,
             dependencies: DependencyTracking::new(),
+            desired: DesiredState::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            targets: Vec::new(),
+            replacements: Vec::new(),
         }
     }
 
+    /// Limits `plan` and `apply` to what matches `query`, like `$.s3_bucket.site["blog"]`
+    /// or `$.module.x.**`, and everything they depend on. Targets add up.
+    pub fn target(&mut self, query: &str) -> Result<(), String> {
+        let parsed = query
+            .parse()
+            .map_err(|e| format!("invalid target {}: {}", query, e))?;
+
+        self.targets.push((query.to_string(), parsed));
+        Ok(())
+    }
+
+    /// Has `plan` and `apply` delete and then create again what matches `query`,
+    /// e.g. when it was changed by hand, even if nothing seems to have changed.
+    pub fn replace(&mut self, query: &str) -> Result<(), String> {
+        let parsed = query
+            .parse()
+            .map_err(|e| format!("invalid replacement {}: {}", query, e))?;
+
+        self.replacements.push((query.to_string(), parsed));
+        Ok(())
+    }
+
+    fn selection(&self) -> Selection {
+        let mut warnings = Vec::new();
+
+        let mut replaced = HashSet::new();
+        for (query, parsed) in &self.replacements {
+            let found = self.dependencies.find(parsed);
+            if found.is_empty() {
+                warnings.push(format!("nothing matches the replacement {}", query));
+            }
+            replaced.extend(found.iter().map(|address| address.path().clone()));
+        }
+
+        if self.targets.is_empty() {
+            return Selection {
+                addresses: None,
+                replaced,
+                warnings,
+            };
+        }
+
+        let targeted: Vec<_> = self
+            .targets
+            .iter()
+            .map(|(query, _)| query.as_str())
+            .collect();
+        warnings.insert(
+            0,
+            format!(
+                "only {} and what they depend on are included, \
+                 so this is a partial result and outputs are not recorded",
+                targeted.join(", ")
+            ),
+        );
+
+        let mut targets = Vec::new();
+        for (query, parsed) in &self.targets {
+            let found = self.dependencies.find(parsed);
+            if found.is_empty() {
+                warnings.push(format!("nothing matches the target {}", query));
+            }
+            targets.extend(found);
+        }
+
+        Selection {
+            addresses: Some(self.dependencies.with_dependencies(&targets)),
+            replaced,
+            warnings,
+        }
+    }
+
+    /// What was defined so far, as far as it is known before apply.
+    pub fn desired(&self) -> &DesiredState {
+        &self.desired
+    }
+
+    /// Records the outputs of the program itself, under `$` in the state.
+    pub fn outputs<T>(&mut self, outputs: T)
+    where
+        T: Serialize + Send + Sync + 'static,
+    {
+        self.outputs.push(Pending::new(
+            AddressPath::Root.to_string(),
+            Arc::new(outputs),
+        ));
+    }
+
     #[instrument(level="info", skip(self, name, builder, dependencies), fields(cloud = %C::NAME))]
     pub fn resource<F, O, const N: usize>(
         &mut self,
@@ -96,18 +261,105 @@ impl<C: Cloud> Provider<C> {
         O: Resource<C> + 'static,
     {
         let object = builder(&mut self.api);
+        let segment = Segment::new(object.kind(), name);
+
+        self.track(segment, object, &dependencies)
+    }
+
+    /// One resource for every item, at `kind.name["key"]`,
+    /// so adding or removing an item leaves the others alone.
+    #[instrument(level="info", skip(self, name, items, builder, dependencies), fields(cloud = %C::NAME))]
+    pub fn resources_for_each<I, K, V, F, O, const N: usize>(
+        &mut self,
+        name: &'static str,
+        items: I,
+        mut builder: F,
+        dependencies: [&dyn AsRef<Address>; N],
+    ) -> Result<BTreeMap<String, Meta<O>>, String>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        F: FnMut(&mut C::ProviderApi, &str, V) -> O,
+        O: Resource<C> + 'static,
+    {
+        let mut resources = BTreeMap::new();
+
+        for (key, item) in items {
+            let key = key.into();
+            if resources.contains_key(&key) {
+                return Err(format!("more than one {} is keyed {:?}", name, key));
+            }
+
+            let object = builder(&mut self.api, &key, item);
+            let segment = Segment::new(object.kind(), name).with_key(key.as_str());
+            let resource = self.track(segment, object, &dependencies);
 
-        let kind = object.kind().to_string();
+            resources.insert(key, resource);
+        }
+
+        Ok(resources)
+    }
+
+    fn track<O>(
+        &mut self,
+        segment: Segment,
+        object: O,
+        dependencies: &[&dyn AsRef<Address>],
+    ) -> Meta<O>
+    where
+        O: Resource<C> + 'static,
+    {
         let wrapped = Arc::new(object);
 
+        let new_address = self.dependencies.child_at(
+            segment,
+            Arc::clone(&wrapped) as Arc<dyn Resource<C>>,
+            DependencyKind::Resource,
+        );
+
+        event!(Level::INFO, "defined resource at {}", new_address);
+
+        for dependency in dependencies {
+            self.dependencies.add_dependency(
+                dependency.as_ref(),
+                &new_address,
+                DependencyKind::Resource,
+            );
+        }
+
+        Meta {
+            inner: wrapped,
+            address: new_address,
+        }
+    }
+
+    /// Reads something that exists already. It is read as part of the graph,
+    /// so resources that use its outputs have to depend on it.
+    #[instrument(level="info", skip(self, name, builder, dependencies), fields(cloud = %C::NAME))]
+    pub fn data<F, D, const N: usize>(
+        &mut self,
+        name: &'static str,
+        builder: F,
+        dependencies: [&dyn AsRef<Address>; N],
+    ) -> Meta<Data<D, D::Outputs>>
+    where
+        F: FnOnce(&mut C::ProviderApi) -> D,
+        D: DataSource<C> + 'static,
+    {
+        let source = builder(&mut self.api);
+
+        // Kept apart from the resource of the same kind
+        let kind = format!("data_{}", source.kind());
+        let wrapped = Arc::new(Data::new(source));
+
         let new_address = self.dependencies.child(
             kind,
             name.to_string(),
-            Arc::clone(&wrapped) as Arc<dyn Creatable<C>>,
+            Arc::clone(&wrapped) as Arc<dyn Resource<C>>,
             DependencyKind::Resource,
         );
 
-        event!(Level::INFO, "defined resource at {}", new_address);
+        event!(Level::INFO, "defined data source at {}", new_address);
 
         for dependency in dependencies {
             self.dependencies.add_dependency(
@@ -123,26 +375,85 @@ impl<C: Cloud> Provider<C> {
         }
     }
 
-    #[instrument(level="info", skip(self, definition, dependencies), fields(module_name, definition = std::any::type_name::<MD>(), cloud = %C::NAME))]
+    /// Everything defined within the module, however deeply nested, is only created
+    /// once its `dependencies` are, and whatever depends on the module waits for all of it.
+    #[instrument(level="info", skip(self, definition, inputs, dependencies), fields(module_name, definition = std::any::type_name::<MD>(), cloud = %C::NAME))]
     pub fn module<MD, const N: usize>(
         &mut self,
         module_name: &'static str,
         definition: MD,
+        inputs: MD::Inputs,
         dependencies: [&dyn AsRef<Address>; N],
-    ) -> Meta<Module<MD, C>>
+    ) -> Result<Meta<Module<MD, C>>, String>
     where
-        MD: ModuleDefinition<C>,
+        MD: ModuleDefinition<C> + 'static,
+        C: 'static,
     {
-        let not_really_the_module = FelipeFakeModule;
-        let new_address = self.dependencies.child(
-            "module",
-            module_name,
-            Arc::new(not_really_the_module),
-            DependencyKind::Module,
-        );
+        let segment = Segment::new("module", module_name);
+
+        self.add_module(module_name, segment, &definition, inputs, &dependencies)
+    }
+
+    /// One module for every item, at `module.name["key"]`, each with its own inputs,
+    /// so adding or removing an item leaves the others alone.
+    #[instrument(level="info", skip(self, definition, items, dependencies), fields(module_name, definition = std::any::type_name::<MD>(), cloud = %C::NAME))]
+    pub fn modules_for_each<I, K, MD, const N: usize>(
+        &mut self,
+        module_name: &'static str,
+        definition: MD,
+        items: I,
+        dependencies: [&dyn AsRef<Address>; N],
+    ) -> Result<BTreeMap<String, Meta<Module<MD, C>>>, String>
+    where
+        I: IntoIterator<Item = (K, MD::Inputs)>,
+        K: Into<String>,
+        MD: ModuleDefinition<C> + 'static,
+        C: 'static,
+    {
+        let mut modules = BTreeMap::new();
+
+        for (key, inputs) in items {
+            let key = key.into();
+            if modules.contains_key(&key) {
+                return Err(format!("more than one {} is keyed {:?}", module_name, key));
+            }
+
+            let segment = Segment::new("module", module_name).with_key(key.as_str());
+            let module =
+                self.add_module(module_name, segment, &definition, inputs, &dependencies)?;
+
+            modules.insert(key, module);
+        }
+
+        Ok(modules)
+    }
+
+    fn add_module<MD>(
+        &mut self,
+        module_name: &'static str,
+        segment: Segment,
+        definition: &MD,
+        inputs: MD::Inputs,
+        dependencies: &[&dyn AsRef<Address>],
+    ) -> Result<Meta<Module<MD, C>>, String>
+    where
+        MD: ModuleDefinition<C> + 'static,
+        C: 'static,
+    {
+        definition
+            .validate(&inputs)
+            .map_err(|e| format!("invalid inputs for module {}: {}", segment, e))?;
+
+        // Never created itself, only what is defined within it
+        let new_address = self.dependencies.group_at(segment, DependencyKind::Module);
+
+        self.desired.set_inputs(new_address.to_string(), &inputs)?;
+        let inputs = Arc::new(inputs);
+        self.inputs
+            .push(Pending::new(new_address.to_string(), Arc::clone(&inputs)));
 
         let old_address = self.dependencies.swap_own_address(new_address);
-        let outputs = definition.define(self);
+        let outputs = definition.define(&inputs, self);
         let new_address = self.dependencies.swap_own_address(old_address);
 
         for dependency in dependencies {
@@ -153,34 +464,209 @@ impl<C: Cloud> Provider<C> {
             );
         }
 
-        Meta {
-            inner: Arc::new(Module {
-                name: module_name,
-                outputs,
-            }),
+        let module = Arc::new(Module {
+            name: module_name,
+            outputs,
+        });
+        self.outputs.push(Pending::new(
+            new_address.to_string(),
+            Arc::new(ModuleOutputs(Arc::clone(&module))),
+        ));
+
+        Ok(Meta {
+            inner: module,
             address: new_address,
+        })
+    }
+
+    /// Reads every resource that is `known` again, so that plans and updates
+    /// go by what is there now rather than by what was recorded last time.
+    /// Tainted resources are left as they are, they are replaced anyway.
+    pub async fn refresh(&self, known: &RealState) -> Result<RealState, ApplyError> {
+        let mut state = known.clone();
+        let selection = self.selection();
+
+        for (resource, address) in self.dependencies.iter() {
+            let recorded = address.to_string();
+            if !selection.includes(address) || known.is_tainted(&recorded) {
+                continue;
+            }
+
+            if let Some(current) = known.resource(&recorded) {
+                event!(Level::INFO, "refreshing {}", address);
+                match resource.read(&self.api, current).await {
+                    Ok(fields) => state.set(ResourceState::new(recorded, fields)),
+                    Err(message) => return Err(ApplyError { state, message }),
+                }
+            }
         }
+
+        Ok(state)
     }
 
-    #[instrument(level="info", skip(self), fields(cloud=C::NAME))]
-    pub async fn create(&self) -> Result<RealState, String> {
-        let mut state = RealState::new();
+    /// What `apply` would do given what is `known` about the world,
+    /// including which modules are applied with different inputs.
+    /// Resources are refreshed first, and left out if they match their definition.
+    pub async fn plan(&self, known: &RealState) -> Result<Plan, String> {
+        let refreshed = self.refresh(known).await?;
+        let selection = self.selection();
+
+        let steps = self
+            .dependencies
+            .iter()
+            .filter(|(_, address)| selection.includes(address))
+            .filter_map(|(resource, address)| {
+                let action = selection.action(resource.as_ref(), address, &refreshed)?;
+                Some(Step {
+                    address: address.to_string(),
+                    action,
+                })
+            })
+            .collect();
+
+        let input_changes = self
+            .desired
+            .inputs()
+            .filter(|(module, _)| selection.touches(module))
+            .filter_map(|(module, after)| {
+                let before = known.inputs(module);
+                if before == Some(after) {
+                    return None;
+                }
+
+                Some(InputChange {
+                    module: module.to_string(),
+                    before: before.cloned(),
+                    after: after.clone(),
+                })
+            })
+            .collect();
+
+        Ok(Plan {
+            steps,
+            input_changes,
+            partial: selection.addresses.is_some(),
+            warnings: selection.warnings,
+        })
+    }
+
+    /// Creates everything from scratch.
+    pub async fn create(&self) -> Result<RealState, ApplyError> {
+        self.apply(&RealState::new()).await
+    }
+
+    /// Brings what is `known` in line with what was defined, as `plan` shows.
+    /// Resources that fail to be created are recorded as tainted,
+    /// so the state returned along with the error is worth saving.
+    #[instrument(level="info", skip(self, known), fields(cloud=C::NAME))]
+    pub async fn apply(&self, known: &RealState) -> Result<RealState, ApplyError> {
+        let known = &self.refresh(known).await?;
+        let mut state = known.clone();
+        let selection = self.selection();
+        for warning in &selection.warnings {
+            event!(Level::WARN, "{}", warning);
+        }
 
         for (resource, address) in self.dependencies.iter() {
-            let fields = resource.create(&self.api).await?;
-            let resource_state = ResourceState::new(address, fields);
+            if !selection.includes(address) {
+                continue;
+            }
+
+            let recorded = address.to_string();
+            let current = known.resource(&recorded);
+            let action = match selection.action(resource.as_ref(), address, known) {
+                Some(action) => action,
+                None => continue,
+            };
+            event!(Level::INFO, "{:?} {}", action, address);
+
+            if let (Action::Update, Some(current)) = (action, current) {
+                match resource.update(&self.api, current).await {
+                    Ok(fields) => state.set(ResourceState::new(recorded, fields)),
+                    // Whatever was recorded before still stands
+                    Err(message) => return Err(ApplyError { state, message }),
+                }
+                continue;
+            }
 
-            state.add(resource_state);
+            // Without anything recorded there is nothing to delete by
+            if let (Action::Replace, Some(current)) = (action, current) {
+                if !current.is_empty() {
+                    if let Err(message) = resource.delete(&self.api, current).await {
+                        return Err(ApplyError { state, message });
+                    }
+                }
+            }
+
+            match resource.create(&self.api).await {
+                Ok(fields) => state.set(ResourceState::new(recorded, fields)),
+                // Some of it might exist, and is deleted before creating it again
+                Err(error) => {
+                    state.set(ResourceState::tainted(recorded, error.fields));
+                    return Err(ApplyError {
+                        state,
+                        message: error.message,
+                    });
+                }
+            }
+        }
+
+        if let Err(message) = self.record(&selection, &mut state) {
+            return Err(ApplyError { state, message });
         }
 
         Ok(state)
     }
+
+    fn record(&self, selection: &Selection, state: &mut RealState) -> Result<(), String> {
+        for inputs in &self.inputs {
+            if !selection.touches(&inputs.address) {
+                continue;
+            }
+
+            let value = (inputs.serialize)()
+                .map_err(|e| format!("could not record the inputs of {}: {}", inputs.address, e))?;
+            state.set_inputs(inputs.address.as_str(), &value)?;
+        }
+
+        // They might need something that wasn't created
+        let outputs = match selection.addresses {
+            Some(_) => &[][..],
+            None => &self.outputs[..],
+        };
+        for outputs in outputs {
+            let value = (outputs.serialize)()
+                .map_err(|e| format!("could not record the outputs of {}: {}", outputs.address, e))?;
+            state.set_outputs(outputs.address.as_str(), &value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Applying stopped part way, with what was applied until then in `state`.
+#[derive(Debug)]
+pub struct ApplyError {
+    pub state: RealState,
+    pub message: String,
+}
+
+impl std::fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<ApplyError> for String {
+    fn from(error: ApplyError) -> Self {
+        error.message
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::Value;
+    use crate::{Creatable, CreateError, Value};
     use async_trait::async_trait;
 
     #[derive(Debug)]
@@ -194,7 +680,31 @@ mod test {
     }
 
     #[async_trait]
-    impl Resource<FakeCloud> for FakeResource {}
+    impl Resource<FakeCloud> for FakeResource {
+        async fn read(
+            &self,
+            _provider: &FakeApi,
+            current: &clutter::Fields,
+        ) -> Result<clutter::Fields, String> {
+            Ok(current.clone())
+        }
+
+        async fn update(
+            &self,
+            _provider: &FakeApi,
+            current: &clutter::Fields,
+        ) -> Result<clutter::Fields, String> {
+            Ok(current.clone())
+        }
+
+        async fn delete(
+            &self,
+            _provider: &FakeApi,
+            _current: &clutter::Fields,
+        ) -> Result<(), String> {
+            Ok(())
+        }
+    }
 
     #[async_trait]
     impl Creatable<FakeCloud> for FakeResource {
@@ -202,7 +712,7 @@ mod test {
             "fake_resource"
         }
 
-        async fn create(&self, _provider: &FakeApi) -> Result<clutter::Fields, String> {
+        async fn create(&self, _provider: &FakeApi) -> Result<clutter::Fields, CreateError> {
             use async_io::Timer;
             use std::time::Duration;
 
@@ -219,7 +729,31 @@ mod test {
     }
 
     #[async_trait]
-    impl Resource<FakeCloud> for OtherResource {}
+    impl Resource<FakeCloud> for OtherResource {
+        async fn read(
+            &self,
+            _provider: &FakeApi,
+            current: &clutter::Fields,
+        ) -> Result<clutter::Fields, String> {
+            Ok(current.clone())
+        }
+
+        async fn update(
+            &self,
+            _provider: &FakeApi,
+            current: &clutter::Fields,
+        ) -> Result<clutter::Fields, String> {
+            Ok(current.clone())
+        }
+
+        async fn delete(
+            &self,
+            _provider: &FakeApi,
+            _current: &clutter::Fields,
+        ) -> Result<(), String> {
+            Ok(())
+        }
+    }
 
     #[async_trait]
     impl Creatable<FakeCloud> for OtherResource {
@@ -227,7 +761,7 @@ mod test {
             "other_resource"
         }
 
-        async fn create(&self, _provider: &FakeApi) -> Result<clutter::Fields, String> {
+        async fn create(&self, _provider: &FakeApi) -> Result<clutter::Fields, CreateError> {
             // TODO: consider a sleep here...
             println!("Creating resource {} with {}", self.name, self.other.get());
             Ok(clutter::Fields::empty())
@@ -267,4 +801,582 @@ mod test {
             assert!(false);
         })
     }
+
+    #[derive(Debug)]
+    struct FakeData(i32);
+
+    #[async_trait]
+    impl DataSource<FakeCloud> for FakeData {
+        type Outputs = i32;
+
+        fn kind(&self) -> &'static str {
+            "fake_data"
+        }
+
+        async fn read(&self, _provider: &FakeApi) -> Result<i32, String> {
+            Ok(self.0)
+        }
+
+        fn fields(&self, outputs: &i32) -> clutter::Fields {
+            clutter::Fields::empty().with_number("value", *outputs)
+        }
+    }
+
+    #[test]
+    fn data_sources_are_read_before_their_dependents() {
+        smol::block_on(async {
+            let mut provider: Provider<FakeCloud> = Provider::new(FakeApi);
+
+            let existing = provider.data("existing", |_api| FakeData(42), []);
+            assert_eq!(existing.outputs(), None);
+            assert_eq!(existing.as_ref().to_string(), "$.data_fake_data.existing");
+
+            let _dependent = provider.resource(
+                "dependent",
+                |_api| OtherResource {
+                    name: "dependent",
+                    other: existing.output(|value| value + 1),
+                },
+                [&existing],
+            );
+
+            provider
+                .create()
+                .await
+                .expect("should have been able to read the data source");
+
+            assert_eq!(existing.outputs(), Some(42));
+            assert_eq!(existing.output(|value| value + 1).get(), 43);
+        })
+    }
+
+    #[derive(Debug)]
+    struct ReadsData;
+
+    impl ModuleDefinition<FakeCloud> for ReadsData {
+        type Inputs = ();
+        type Outputs = Value<i32>;
+
+        fn define(&self, _inputs: &(), provider: &mut Provider<FakeCloud>) -> Value<i32> {
+            let existing = provider.data("existing", |_api| FakeData(42), []);
+            existing.output(|value| *value)
+        }
+    }
+
+    #[test]
+    fn outputs_are_recorded_once_everything_exists() {
+        smol::block_on(async {
+            let mut provider: Provider<FakeCloud> = Provider::new(FakeApi);
+
+            let module = provider.module("reads-data", ReadsData, (), []).unwrap();
+            provider.outputs(vec![module.outputs()]);
+
+            let state = provider
+                .create()
+                .await
+                .expect("should have been able to read the data source");
+
+            assert!(state.resource("$.module.reads-data").is_none());
+            assert!(state
+                .resource("$.module.reads-data.data_fake_data.existing")
+                .is_some());
+            assert_eq!(state.outputs::<i32>("$.module.reads-data"), Ok(42));
+            assert_eq!(state.outputs::<Vec<i32>>("$"), Ok(vec![42]));
+        })
+    }
+
+    #[test]
+    fn resources_and_modules_are_recorded_at_the_addresses_they_show() {
+        smol::block_on(async {
+            let mut provider: Provider<FakeCloud> = Provider::new(FakeApi);
+
+            let existing = provider.data("existing", |_api| FakeData(1), []);
+            let module = provider.module("reads-data", ReadsData, (), []).unwrap();
+
+            let state = provider.create().await.unwrap();
+            assert!(state.resource(&existing.as_ref().to_string()).is_some());
+            assert_eq!(state.outputs::<i32>(&module.as_ref().to_string()), Ok(42));
+            assert!(state.addresses().all(|address| address.starts_with("$.")));
+        })
+    }
+
+    #[derive(Debug)]
+    struct Offset;
+
+    impl ModuleDefinition<FakeCloud> for Offset {
+        type Inputs = Value<i32>;
+        type Outputs = ();
+
+        fn validate(&self, inputs: &Value<i32>) -> Result<(), String> {
+            match inputs.try_get() {
+                Some(offset) if offset < 0 => Err("offset can't be negative".into()),
+                _ => Ok(()),
+            }
+        }
+
+        fn define(&self, inputs: &Value<i32>, provider: &mut Provider<FakeCloud>) {
+            let offset = inputs.clone();
+            provider.resource(
+                "offset",
+                move |_api| OtherResource {
+                    name: "offset",
+                    other: offset,
+                },
+                [],
+            );
+        }
+    }
+
+    #[test]
+    fn module_inputs_are_validated_and_changes_show_up_in_plans() {
+        smol::block_on(async {
+            let mut provider: Provider<FakeCloud> = Provider::new(FakeApi);
+            assert!(provider
+                .module("negative", Offset, Value::Real(-1), [])
+                .is_err());
+
+            let existing = provider.data("existing", |_api| FakeData(42), []);
+            provider
+                .module(
+                    "offset",
+                    Offset,
+                    existing.output(|value| *value),
+                    [&existing],
+                )
+                .unwrap();
+
+            let plan = provider.plan(&RealState::new()).await.unwrap();
+            assert!(plan.steps.iter().all(|step| step.action == Action::Create));
+            assert_eq!(
+                plan.input_changes,
+                vec![InputChange {
+                    module: "$.module.offset".into(),
+                    before: None,
+                    after: serde_json::json!(crate::KNOWN_AFTER_APPLY),
+                }]
+            );
+
+            let mut provider: Provider<FakeCloud> = Provider::new(FakeApi);
+            provider
+                .module("offset", Offset, Value::Real(42), [])
+                .unwrap();
+            let mut known = provider.create().await.unwrap();
+            assert!(provider
+                .plan(&known)
+                .await
+                .unwrap()
+                .steps
+                .iter()
+                .all(|step| step.action == Action::Update));
+            assert_eq!(
+                known.inputs("$.module.offset"),
+                Some(&serde_json::json!(42))
+            );
+
+            let mut provider: Provider<FakeCloud> = Provider::new(FakeApi);
+            provider
+                .module("offset", Offset, Value::Real(7), [])
+                .unwrap();
+
+            let plan = provider.plan(&known).await.unwrap();
+            assert_eq!(plan.input_changes.len(), 1);
+            assert_eq!(plan.input_changes[0].before, Some(serde_json::json!(42)));
+            assert_eq!(plan.input_changes[0].after, serde_json::json!(7));
+            assert!(plan
+                .to_string()
+                .contains("inputs of $.module.offset changed"));
+
+            known.set_inputs("$.module.offset", &7).unwrap();
+            assert!(provider
+                .plan(&known)
+                .await
+                .unwrap()
+                .input_changes
+                .is_empty());
+        })
+    }
+
+    #[derive(Debug)]
+    struct Nested;
+
+    impl ModuleDefinition<FakeCloud> for Nested {
+        type Inputs = Value<i32>;
+        type Outputs = ();
+
+        fn define(&self, inputs: &Value<i32>, provider: &mut Provider<FakeCloud>) {
+            provider
+                .module("offset", Offset, inputs.clone(), [])
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn module_dependencies_gate_everything_within() {
+        smol::block_on(async {
+            let mut provider: Provider<FakeCloud> = Provider::new(FakeApi);
+
+            // Defined before the data source it needs, which has to be read first
+            let nested = provider
+                .module("nested", Nested, Value::Real(0), [])
+                .unwrap();
+            let existing = provider.data("existing", |_api| FakeData(42), []);
+            let _outer = provider
+                .module(
+                    "outer",
+                    Nested,
+                    existing.output(|value| *value),
+                    [&existing, &nested],
+                )
+                .unwrap();
+
+            let state = provider
+                .create()
+                .await
+                .expect("the data source should have been read before the module");
+
+            assert_eq!(
+                state.inputs("$.module.outer.module.offset"),
+                Some(&serde_json::json!(42))
+            );
+        })
+    }
+
+    #[test]
+    fn items_get_their_own_addresses() {
+        smol::block_on(async {
+            let mut provider: Provider<FakeCloud> = Provider::new(FakeApi);
+
+            let sites = provider
+                .resources_for_each(
+                    "site",
+                    vec![("blog", 1), ("shop", 2)],
+                    |_api, key, value| {
+                        assert!(key == "blog" || key == "shop");
+                        Data::new(FakeData(value))
+                    },
+                    [],
+                )
+                .unwrap();
+            assert_eq!(
+                sites["shop"].as_ref().to_string(),
+                r#"$.fake_data.site["shop"]"#
+            );
+
+            let offsets = provider
+                .modules_for_each(
+                    "offset",
+                    Offset,
+                    vec![("blog", sites["blog"].output(|value| *value))],
+                    [&sites["blog"]],
+                )
+                .unwrap();
+            assert_eq!(
+                offsets["blog"].as_ref().to_string(),
+                r#"$.module.offset["blog"]"#
+            );
+
+            assert!(provider
+                .resources_for_each(
+                    "twice",
+                    vec![("blog", 1), ("blog", 2)],
+                    |_api, _key, value| Data::new(FakeData(value)),
+                    [],
+                )
+                .is_err());
+            assert!(provider
+                .modules_for_each(
+                    "twice",
+                    Offset,
+                    vec![("blog", Value::Real(1)), ("blog", Value::Real(2))],
+                    [],
+                )
+                .is_err());
+
+            let state = provider.create().await.unwrap();
+            assert!(state.resource(r#"$.fake_data.site["blog"]"#).is_some());
+            assert!(state
+                .resource(r#"$.module.offset["blog"].other_resource.offset"#)
+                .is_some());
+        })
+    }
+
+    #[test]
+    fn targets_limit_plans_to_them_and_their_dependencies() {
+        smol::block_on(async {
+            let mut provider: Provider<FakeCloud> = Provider::new(FakeApi);
+
+            let existing = provider.data("existing", |_api| FakeData(42), []);
+            let _unrelated = provider.data("unrelated", |_api| FakeData(0), []);
+            provider
+                .module(
+                    "offset",
+                    Offset,
+                    existing.output(|value| *value),
+                    [&existing],
+                )
+                .unwrap();
+            provider.outputs(existing.output(|value| *value));
+
+            assert!(provider.target("$.module.offset[").is_err());
+            provider.target("$.module.offset.**").unwrap();
+            provider.target("$.s3_bucket.missing").unwrap();
+
+            let plan = provider.plan(&RealState::new()).await.unwrap();
+            assert!(plan.partial);
+            let addresses: Vec<_> = plan
+                .steps
+                .iter()
+                .map(|step| step.address.as_str())
+                .collect();
+            assert_eq!(
+                addresses,
+                vec![
+                    "$.data_fake_data.existing",
+                    "$.module.offset.other_resource.offset"
+                ]
+            );
+            assert_eq!(plan.input_changes.len(), 1);
+            assert_eq!(plan.warnings.len(), 2);
+            assert!(plan
+                .to_string()
+                .starts_with("warning: only $.module.offset.**"));
+
+            let state = provider.create().await.unwrap();
+            assert!(state.resource("$.data_fake_data.unrelated").is_none());
+            assert!(state
+                .resource("$.module.offset.other_resource.offset")
+                .is_some());
+            assert_eq!(
+                state.inputs("$.module.offset"),
+                Some(&serde_json::json!(42))
+            );
+            assert!(state.outputs::<i32>("$").is_err());
+        })
+    }
+
+    /// Keeps track of what was done to it, and fails to be created if asked to,
+    /// after creating what `fail` describes.
+    #[derive(Debug)]
+    struct Recorder {
+        calls: Arc<std::sync::Mutex<Vec<&'static str>>>,
+        fail: Option<clutter::Fields>,
+    }
+
+    #[async_trait]
+    impl Creatable<FakeCloud> for Recorder {
+        fn kind(&self) -> &'static str {
+            "recorder"
+        }
+
+        async fn create(&self, _provider: &FakeApi) -> Result<clutter::Fields, CreateError> {
+            self.calls.lock().unwrap().push("create");
+            match &self.fail {
+                Some(created) => Err(CreateError::partly(
+                    created.clone(),
+                    "could not finish creating it",
+                )),
+                None => Ok(clutter::Fields::empty().with_text("id", "recorded")),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Resource<FakeCloud> for Recorder {
+        async fn read(
+            &self,
+            _provider: &FakeApi,
+            current: &clutter::Fields,
+        ) -> Result<clutter::Fields, String> {
+            Ok(current.clone())
+        }
+
+        async fn update(
+            &self,
+            _provider: &FakeApi,
+            current: &clutter::Fields,
+        ) -> Result<clutter::Fields, String> {
+            self.calls.lock().unwrap().push("update");
+            Ok(current.clone())
+        }
+
+        async fn delete(
+            &self,
+            _provider: &FakeApi,
+            current: &clutter::Fields,
+        ) -> Result<(), String> {
+            current
+                .text("id")
+                .ok_or("no id recorded for the recorder")?;
+            self.calls.lock().unwrap().push("delete");
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn replaced_and_tainted_resources_are_deleted_and_created_again() {
+        smol::block_on(async {
+            let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let recorder = |fail| {
+                let calls = Arc::clone(&calls);
+                move |_api: &mut FakeApi| Recorder { calls, fail }
+            };
+            let partly = clutter::Fields::empty().with_text("id", "partly");
+
+            let mut provider: Provider<FakeCloud> = Provider::new(FakeApi);
+            provider.resource("object", recorder(None), []);
+            provider.resource("broken", recorder(Some(partly.clone())), []);
+
+            let failed = provider.create().await.unwrap_err();
+            assert_eq!(failed.message, "could not finish creating it");
+            let known = RealState::from_json(&failed.state.to_json()).unwrap();
+            assert!(known.is_tainted("$.recorder.broken"));
+            assert!(!known.is_tainted("$.recorder.object"));
+            assert_eq!(known.resource("$.recorder.broken"), Some(&partly));
+
+            let mut provider: Provider<FakeCloud> = Provider::new(FakeApi);
+            provider.resource("object", recorder(None), []);
+            provider.resource("broken", recorder(None), []);
+
+            let actions: BTreeMap<_, _> = provider
+                .plan(&known)
+                .await
+                .unwrap()
+                .steps
+                .into_iter()
+                .map(|step| (step.address, step.action))
+                .collect();
+            assert_eq!(actions["$.recorder.broken"], Action::Replace);
+            assert_eq!(actions["$.recorder.object"], Action::Update);
+
+            provider.replace("$.recorder.object").unwrap();
+            provider.replace("$.recorder.missing").unwrap();
+            let plan = provider.plan(&known).await.unwrap();
+            assert!(plan.steps.iter().all(|step| step.action == Action::Replace));
+            assert_eq!(plan.warnings.len(), 1);
+            assert!(plan.to_string().contains("-/+ $.recorder.object"));
+
+            calls.lock().unwrap().clear();
+            let applied = provider.apply(&known).await.unwrap();
+            assert!(!applied.is_tainted("$.recorder.broken"));
+            assert_eq!(applied.addresses().count(), 2);
+            assert_eq!(
+                *calls.lock().unwrap(),
+                vec!["delete", "create", "delete", "create"]
+            );
+        })
+    }
+
+    #[test]
+    fn nothing_is_deleted_if_nothing_was_created() {
+        smol::block_on(async {
+            let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let recorder = |fail| {
+                let calls = Arc::clone(&calls);
+                move |_api: &mut FakeApi| Recorder { calls, fail }
+            };
+
+            let mut provider: Provider<FakeCloud> = Provider::new(FakeApi);
+            provider.resource("broken", recorder(Some(clutter::Fields::empty())), []);
+            let known = provider.create().await.unwrap_err().state;
+            assert!(known.is_tainted("$.recorder.broken"));
+
+            let mut provider: Provider<FakeCloud> = Provider::new(FakeApi);
+            provider.resource("broken", recorder(None), []);
+            let applied = provider.apply(&known).await.unwrap();
+
+            assert!(!applied.is_tainted("$.recorder.broken"));
+            assert_eq!(*calls.lock().unwrap(), vec!["create", "create"]);
+        })
+    }
+
+    /// Reads back as `actual`, whatever was recorded, and remembers what it was updated from.
+    #[derive(Debug)]
+    struct Drifting {
+        actual: i32,
+        desired: i32,
+        updated: Arc<std::sync::Mutex<Vec<clutter::Fields>>>,
+    }
+
+    #[async_trait]
+    impl Creatable<FakeCloud> for Drifting {
+        fn kind(&self) -> &'static str {
+            "drifting"
+        }
+
+        async fn create(&self, _provider: &FakeApi) -> Result<clutter::Fields, CreateError> {
+            Ok(clutter::Fields::empty().with_number("value", self.desired))
+        }
+    }
+
+    #[async_trait]
+    impl Resource<FakeCloud> for Drifting {
+        async fn read(
+            &self,
+            _provider: &FakeApi,
+            current: &clutter::Fields,
+        ) -> Result<clutter::Fields, String> {
+            Ok(current.clone().with_number("value", self.actual))
+        }
+
+        async fn update(
+            &self,
+            _provider: &FakeApi,
+            current: &clutter::Fields,
+        ) -> Result<clutter::Fields, String> {
+            self.updated.lock().unwrap().push(current.clone());
+            Ok(current.clone().with_number("value", self.desired))
+        }
+
+        async fn delete(
+            &self,
+            _provider: &FakeApi,
+            _current: &clutter::Fields,
+        ) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn drifted(&self, current: &clutter::Fields) -> bool {
+            current.number("value") != Some(self.desired)
+        }
+    }
+
+    #[test]
+    fn drift_is_planned_and_updated_from_what_was_read() {
+        smol::block_on(async {
+            let updated = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let drifting = |actual| {
+                let updated = Arc::clone(&updated);
+                move |_api: &mut FakeApi| Drifting {
+                    actual,
+                    desired: 1,
+                    updated,
+                }
+            };
+
+            let mut provider: Provider<FakeCloud> = Provider::new(FakeApi);
+            provider.resource("changed", drifting(2), []);
+            provider.resource("unchanged", drifting(1), []);
+
+            let known = provider.create().await.unwrap();
+            let value = |state: &RealState, address| state.resource(address)?.number("value");
+            assert_eq!(value(&known, "$.drifting.changed"), Some(1));
+
+            let plan = provider.plan(&known).await.unwrap();
+            assert_eq!(
+                plan.steps,
+                vec![Step {
+                    address: "$.drifting.changed".into(),
+                    action: Action::Update,
+                }]
+            );
+
+            let applied = provider.apply(&known).await.unwrap();
+            assert_eq!(value(&applied, "$.drifting.changed"), Some(1));
+            assert_eq!(value(&applied, "$.drifting.unchanged"), Some(1));
+
+            let updated = updated.lock().unwrap();
+            assert_eq!(updated.len(), 1);
+            assert_eq!(updated[0].number("value"), Some(2));
+        })
+    }
 }
@@ -0,0 +1,114 @@
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::{Cloud, DataSource, Fields, RealState};
+
+/// The outputs another program recorded for one of its modules,
+/// read from the state it saved to a local file.
+/// `T` is the `Outputs` type of that module, or anything shaped like it.
+pub struct RemoteState<T> {
+    path: PathBuf,
+    module: String,
+    outputs: PhantomData<fn() -> T>,
+}
+
+impl<T> RemoteState<T> {
+    /// Use `$` as the `module` for the outputs of the program itself.
+    pub fn file(path: impl Into<PathBuf>, module: impl Into<String>) -> Self {
+        RemoteState {
+            path: path.into(),
+            module: module.into(),
+            outputs: PhantomData,
+        }
+    }
+}
+
+impl<T> Debug for RemoteState<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteState")
+            .field("path", &self.path)
+            .field("module", &self.module)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<C, T> DataSource<C> for RemoteState<T>
+where
+    C: Cloud,
+    T: DeserializeOwned + Serialize + Clone + Debug + Send + Sync + 'static,
+{
+    type Outputs = T;
+
+    fn kind(&self) -> &'static str {
+        "remote_state"
+    }
+
+    async fn read(&self, _provider: &<C as Cloud>::ProviderApi) -> Result<T, String> {
+        RealState::load(&self.path)?.outputs(&self.module)
+    }
+
+    fn fields(&self, outputs: &T) -> Fields {
+        // Outputs that don't fit into fields, like fractional numbers, are left out
+        let outputs = Fields::serialized(outputs).unwrap_or_else(|_| Fields::empty());
+
+        Fields::empty()
+            .with_text("path", self.path.to_string_lossy())
+            .with_text("module", &self.module)
+            .with_object("outputs", |_| outputs.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    struct FakeCloud;
+
+    impl Cloud for FakeCloud {
+        type ProviderApi = ();
+        const NAME: &'static str = "FakeCloud";
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct Network {
+        log_bucket_arn: String,
+    }
+
+    #[test]
+    fn outputs_are_read_from_the_saved_state() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("luminary.state.json");
+        let mut state = RealState::new();
+        state
+            .set_outputs(
+                "$.module.network",
+                &Network {
+                    log_bucket_arn: "arn:aws:s3:::shared-logs".into(),
+                },
+            )
+            .unwrap();
+        state.save(&path).unwrap();
+
+        let remote = RemoteState::<Network>::file(&path, "$.module.network");
+        let network = smol::block_on(DataSource::<FakeCloud>::read(&remote, &())).unwrap();
+        assert_eq!(network.log_bucket_arn, "arn:aws:s3:::shared-logs");
+
+        let fields = DataSource::<FakeCloud>::fields(&remote, &network);
+        assert_eq!(
+            fields
+                .object("outputs")
+                .and_then(|outputs| outputs.text("log_bucket_arn")),
+            Some("arn:aws:s3:::shared-logs")
+        );
+
+        let missing = RemoteState::<Network>::file(&path, "$.module.storage");
+        assert!(smol::block_on(DataSource::<FakeCloud>::read(&missing, &())).is_err());
+    }
+}
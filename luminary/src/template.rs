@@ -0,0 +1,141 @@
+use crate::Value;
+
+/// Text with `${name}` placeholders that are filled in with `Value`s,
+/// so it can embed things that are only known once other resources exist.
+/// Nothing is resolved until the template is rendered, which should happen at apply time.
+/// A literal `$` is written as `$$`.
+#[derive(Clone, Debug)]
+pub struct Template {
+    text: String,
+    values: Vec<(String, Value<String>)>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Part<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+fn parse(text: &str) -> Result<Vec<Part<'_>>, String> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('$') {
+        if start > 0 {
+            parts.push(Part::Text(&rest[..start]));
+        }
+        rest = &rest[start + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            parts.push(Part::Text("$"));
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('{') {
+            let end = after
+                .find('}')
+                .ok_or_else(|| format!("unclosed placeholder in template at {}", rest))?;
+            let name = after[..end].trim();
+            if name.is_empty() {
+                return Err("empty placeholder in template".into());
+            }
+            parts.push(Part::Placeholder(name));
+            rest = &after[end + 1..];
+        } else {
+            return Err("a `$` in a template has to be escaped as `$$`".into());
+        }
+    }
+
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest));
+    }
+
+    Ok(parts)
+}
+
+impl Template {
+    pub fn new(text: impl Into<String>) -> Self {
+        Template {
+            text: text.into(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Fills in `${name}`.
+    pub fn value(mut self, name: impl Into<String>, value: impl Into<Value<String>>) -> Self {
+        self.values.push((name.into(), value.into()));
+        self
+    }
+
+    /// Checks that the template is well formed and every placeholder has a value,
+    /// without resolving any of them.
+    pub fn validate(&self) -> Result<(), String> {
+        for part in parse(&self.text)? {
+            if let Part::Placeholder(name) = part {
+                self.lookup(name)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn render(&self) -> Result<String, String> {
+        let mut rendered = String::with_capacity(self.text.len());
+
+        for part in parse(&self.text)? {
+            match part {
+                Part::Text(text) => rendered.push_str(text),
+                Part::Placeholder(name) => rendered.push_str(&self.lookup(name)?.get()),
+            }
+        }
+
+        Ok(rendered)
+    }
+
+    fn lookup(&self, name: &str) -> Result<&Value<String>, String> {
+        self.values
+            .iter()
+            .find(|(candidate, _)| candidate == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| format!("no value for ${{{}}} in template", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn placeholders_are_resolved_when_rendering() {
+        let arn = Arc::new(Mutex::new(None::<String>));
+        let late = Arc::clone(&arn);
+
+        let template =
+            Template::new(r#"{"bucket": "${bucket}", "role": "${ role }", "cost": "$$5"}"#)
+                .value("bucket", "my-site".to_string())
+                .value(
+                    "role",
+                    Value::Reference(Box::new(move || {
+                        late.lock().unwrap().clone().unwrap_or_default()
+                    })),
+                );
+        assert!(template.validate().is_ok());
+
+        *arn.lock().unwrap() = Some("arn:aws:iam::123456789012:role/site".into());
+        assert_eq!(
+            template.render().unwrap(),
+            r#"{"bucket": "my-site", "role": "arn:aws:iam::123456789012:role/site", "cost": "$5"}"#
+        );
+    }
+
+    #[test]
+    fn malformed_templates_are_rejected() {
+        assert!(Template::new("${missing}").validate().is_err());
+        assert!(Template::new("${unclosed").validate().is_err());
+        assert!(Template::new("${}").validate().is_err());
+        assert!(Template::new("costs $5").validate().is_err());
+        assert_eq!(
+            parse("a${b}c").unwrap(),
+            vec![Part::Text("a"), Part::Placeholder("b"), Part::Text("c")]
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use std::fmt::Debug;
 
+use serde::{Serialize, Serializer};
+
 use crate::Produce;
 
 pub enum Value<T> {
@@ -35,6 +37,13 @@ impl<T: Clone + 'static> Value<T> {
         }
     }
 
+    pub fn try_get(&self) -> Option<T> {
+        match self {
+            Value::Real(ref s) => Some(s.clone()),
+            Value::Reference(producer) => producer.try_get(),
+        }
+    }
+
     fn map<F, U>(&self, transform: F) -> Value<U>
     where
         F: 'static + Clone + Send + Sync + Fn(T) -> U,
@@ -53,6 +62,19 @@ impl<T: Clone + 'static> Value<T> {
     }
 }
 
+/// What values that are only known after apply are recorded as until then.
+pub const KNOWN_AFTER_APPLY: &str = "(known after apply)";
+
+/// Resolves the value, or records `KNOWN_AFTER_APPLY` when it isn't known yet.
+impl<T: Serialize + Clone + 'static> Serialize for Value<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.try_get() {
+            Some(value) => value.serialize(serializer),
+            None => serializer.serialize_str(KNOWN_AFTER_APPLY),
+        }
+    }
+}
+
 // We'd have more impls for basic things here
 impl From<String> for Value<String> {
     fn from(content: String) -> Self {
@@ -60,6 +82,12 @@ impl From<String> for Value<String> {
     }
 }
 
+impl From<&str> for Value<String> {
+    fn from(content: &str) -> Self {
+        Value::Real(content.to_string())
+    }
+}
+
 impl From<&'static str> for Value<&'static str> {
     fn from(content: &'static str) -> Self {
         Value::Real(content)